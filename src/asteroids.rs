@@ -1,4 +1,7 @@
-use macroquad::{prelude::*, rand::gen_range};
+use glam::{vec2, Vec2};
+
+use crate::rng::Rng;
+
 #[derive(Clone)]
 pub enum AsteroidSize {
     Large,
@@ -11,25 +14,25 @@ pub struct Asteroid {
     pub pos: Vec2,
     pub vel: Vec2,
     pub size: AsteroidSize,
-    sides: u8,
+    pub sides: u8,
     pub radius: f32,
-    rot: f32,
+    pub rot: f32,
     omega: f32,
     pub alive: bool,
 }
 
 impl Asteroid {
-    pub fn new(size: AsteroidSize, (WIDTH, HEIGHT): (f32, f32)) -> Self {
+    pub fn new(size: AsteroidSize, (WIDTH, HEIGHT): (f32, f32), rng: &mut Rng) -> Self {
         let (sides, radius) = match size {
-            AsteroidSize::Large => (gen_range(6, 10), gen_range(50., 65.)),
-            AsteroidSize::Medium => (gen_range(5, 6), gen_range(35., 50.)),
-            AsteroidSize::Small => (gen_range(3, 5), 25.),
+            AsteroidSize::Large => (rng.gen_range(6, 10), rng.gen_range(50., 65.)),
+            AsteroidSize::Medium => (rng.gen_range(5, 6), rng.gen_range(35., 50.)),
+            AsteroidSize::Small => (rng.gen_range(3, 5), 25.),
         };
         let mut r = vec2(
-            if gen_range(0., 1.) > 0.5 { -1. } else { 1. },
-            gen_range(-1., 1.),
+            if rng.gen_range(0., 1.) > 0.5 { -1. } else { 1. },
+            rng.gen_range(-1., 1.),
         );
-        if gen_range(0., 1.) > 0.5 {
+        if rng.gen_range(0., 1.) > 0.5 {
             r = vec2(r.y, r.x);
         }
         r *= vec2(WIDTH * 0.5 + radius, HEIGHT * 0.5 + radius);
@@ -37,27 +40,39 @@ impl Asteroid {
             pos: r,
             vel: 0.001 * -r
                 + vec2(
-                    gen_range(0.3, 1.) * if gen_range(0., 1.) > 0.5 { -1. } else { 1. },
-                    gen_range(0.3, 1.) * if gen_range(0., 1.) > 0.5 { -1. } else { 1. },
+                    rng.gen_range(0.3, 1.) * if rng.gen_range(0., 1.) > 0.5 { -1. } else { 1. },
+                    rng.gen_range(0.3, 1.) * if rng.gen_range(0., 1.) > 0.5 { -1. } else { 1. },
                 ),
             size,
             sides,
             radius,
-            omega: gen_range(0.8, 3.5) * if gen_range(0., 1.) > 0.5 { -1. } else { 1. },
+            omega: rng.gen_range(0.8, 3.5) * if rng.gen_range(0., 1.) > 0.5 { -1. } else { 1. },
             rot: 0.,
             alive: true,
         }
     }
 
-    pub fn new_from(pos: Vec2, vel: Vec2, size: AsteroidSize, (WIDTH, HEIGHT): (f32, f32)) -> Self {
-        let mut asteroid = Asteroid::new(size, (WIDTH, HEIGHT));
+    pub fn new_from(
+        pos: Vec2,
+        vel: Vec2,
+        size: AsteroidSize,
+        (WIDTH, HEIGHT): (f32, f32),
+        rng: &mut Rng,
+    ) -> Self {
+        let mut asteroid = Asteroid::new(size, (WIDTH, HEIGHT), rng);
         asteroid.pos = pos;
         asteroid.vel = vel;
         asteroid
     }
 
-    pub fn new_to(pos: Vec2, speed: f32, size: AsteroidSize, (WIDTH, HEIGHT): (f32, f32)) -> Self {
-        let mut asteroid = Asteroid::new(size, (WIDTH, HEIGHT));
+    pub fn new_to(
+        pos: Vec2,
+        speed: f32,
+        size: AsteroidSize,
+        (WIDTH, HEIGHT): (f32, f32),
+        rng: &mut Rng,
+    ) -> Self {
+        let mut asteroid = Asteroid::new(size, (WIDTH, HEIGHT), rng);
         asteroid.vel = (pos - asteroid.pos) * 0.002 * speed;
         asteroid
     }
@@ -77,20 +92,4 @@ impl Asteroid {
             self.pos.y *= -1.;
         }
     }
-
-    pub fn draw(&self, color: Color) {
-        draw_poly_lines(
-            self.pos.x,
-            self.pos.y,
-            self.sides,
-            self.radius,
-            self.rot,
-            match self.size {
-                AsteroidSize::Large => 2.,
-                AsteroidSize::Medium => 1.2,
-                AsteroidSize::Small => 1.,
-            },
-            color,
-        );
-    }
 }
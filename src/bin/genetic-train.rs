@@ -0,0 +1,504 @@
+#![allow(non_snake_case)]
+
+use std::{
+    process::exit,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use genetic::{
+    brain::BrainKind,
+    config::Config,
+    fitness::{Aggregate, FitnessFn},
+    islands::{Islands, Topology},
+    nn::{ActivationFunc, Crossover, Mutation},
+    novelty::Novelty,
+    population::Population,
+    rng::Rng,
+    selection::Selection,
+    stopping::StopConditions,
+};
+
+const USAGE: &str = "Usage: genetic-train [OPTIONS]
+
+Trains a population without opening a window.
+
+Options:
+    --generations <N>   Stop after N generations (default: 100 if no other stop
+                        condition is given)
+    --target <F>        Stop once the best fitness of a generation reaches F
+    --patience <N>      Stop after N generations in a row without a new best fitness
+    --time-limit <S>    Stop at the end of the first generation to finish S seconds
+                        or more after the start
+    --size <N>          Population size (default: 100)
+    --config <PATH>     Load GA settings from a JSON file, options after it override it
+    --brain <B>         Kind of network: layered, or neat[:<ADD_CONN>,<ADD_NODE>] to
+                        grow the topology with the given chances per child
+                        (default: layered, neat alone uses 0.05,0.03)
+    --speciation        Group similar networks into species that breed among
+                        themselves, always on with --brain neat
+    --species-threshold <D>
+                        Distance under which brains share a species; NEAT genomes
+                        differ by about 1 per new gene, layered networks by their
+                        mean weight difference (default: 3 for NEAT, 0.5 for
+                        layered)
+    --stagnation <N>    Generations a species may go without improving before it
+                        gets no children, 0 to never cull (default: 15)
+    --hlayers <A,B,C>   Neurons in each hidden layer (default: 6,6)
+    --recurrent         Feed the output of every hidden layer back into it next frame
+    --mut-rate <R>      Mutation rate (default: 0.05)
+    --mutation <M>      How mutated weights change: reset, gaussian:<SIGMA> or
+                        scaled:<SIGMA> (default: reset)
+    --self-adaptive     Let every network evolve its own mutation rate and sigma
+    --crossover <C>     How parents are combined: uniform, single, multi:<POINTS>,
+                        neuron, blend:<ALPHA> or none (default: uniform)
+    --activ <F>         Activation function: relu, sigmoid, tanh, leaky, elu, softsign,
+                        gaussian, identity or step (default: relu)
+    --hidden-activs <A,B,C>
+                        Activation of each hidden layer, default to use --activ
+    --out-activ <F>     Activation of the output layer (default: same as --activ)
+    --fitness <F>       Fitness function: default, survival, kills, accuracy or
+                        weighted:<LIFESPAN>,<KILLS>,<ACCURACY>, floored at 0
+                        (default: default)
+    --selection <S>     Parent selection: roulette, tournament:<K>, rank, sus or
+                        truncation:<FRACTION> (default: roulette)
+    --elitism <F>       Fraction of the population copied unchanged into the next
+                        generation, 0 to disable (default: 0.05)
+    --elite-evals <N>   Runs averaged to pick the elites among twice as many
+                        candidates (default: 1)
+    --hall-of-fame <K>[,R]
+                        Best brains of all generations kept, R of them rejoin every
+                        generation in place of children (default: 10, 0)
+    --common-scenario   Every brain of a generation plays the same asteroid field
+    --episodes <N>[,A]  Asteroid fields every brain plays, with scores combined by
+                        A: mean, min or median (default: 1, mean)
+    --sequential-episodes
+                        Play extra episodes after the first instead of alongside it,
+                        which is slower but holds one world per brain in memory
+    --islands <N>[,T[,I[,M]]]
+                        Split the population into N islands that breed separately,
+                        sending their M best ships every I generations to the next
+                        island (T: ring) or to all others (T: full)
+                        (default T: ring, I: 10, M: 2)
+    --multi-objective   Rank ships on lifespan, kills and accuracy with NSGA-II
+                        instead of on the fitness function
+    --novelty <W>[,K[,A[,C]]]
+                        Select on novelty of behavior with weight W against fitness,
+                        measured to the K nearest behaviors, archiving the A most
+                        novel each generation in an archive of at most C
+                        (default K: 15, A: 2, C: 500)
+    --width <W>         Arena width (default: 800)
+    --height <H>        Arena height (default: 780)
+    --threads <N>       Worker threads used to simulate worlds (default: all cores)
+    --seed <S>          Seed for the random number generator (default: clock)
+    --stream <S>        Stream of the random number generator (default: 0)
+    --out <PATH>        Save the best model to PATH when training ends
+                        (default: best_model.json)
+    --summary <PATH>    Save a summary of the run to PATH when training ends
+                        (default: summary.json)
+    --hall <PATH>       Save the hall of fame to PATH when training ends
+    --checkpoint <PATH> Save the whole population to PATH when training ends
+    --checkpoint-every <N>
+                        Also save the checkpoint every N generations (default: 10)
+    --log <PATH>        Write stats of every generation to PATH, as CSV if it ends
+                        in .csv and as JSON Lines otherwise
+    --lineage <PATH>    Write every brain of every generation with its parents and
+                        mutations to PATH, as CSV if it ends in .csv and as JSON
+                        Lines otherwise
+    --resume <PATH>     Resume from a checkpoint, which decides the population, seed
+                        and GA options, so only stop conditions, outputs, --width,
+                        --height and --threads can be given with it; --generations
+                        counts from generation 0
+    -h, --help          Print this help";
+
+// Options that still apply when resuming, the checkpoint decides all others
+const RUN_OPTIONS: &[&str] = &[
+    "--generations",
+    "--target",
+    "--patience",
+    "--time-limit",
+    "--width",
+    "--height",
+    "--threads",
+    "--out",
+    "--summary",
+    "--hall",
+    "--checkpoint",
+    "--checkpoint-every",
+    "--log",
+    "--lineage",
+    "--resume",
+    "-h",
+    "--help",
+];
+
+struct Args {
+    stop: StopConditions,
+    size: usize,
+    config: Config,
+    width: f32,
+    height: f32,
+    threads: usize,
+    seed: Option<u64>,
+    stream: u64,
+    out: String,
+    summary: String,
+    hall: Option<String>,
+    checkpoint: Option<String>,
+    checkpoint_every: i32,
+    resume: Option<String>,
+    log: Option<String>,
+    lineage: Option<String>,
+    // First option given that a resumed checkpoint decides
+    decided: Option<String>,
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("error: {}\n\n{}", msg, USAGE);
+    exit(2);
+}
+
+fn value<T: std::str::FromStr>(args: &mut impl Iterator<Item = String>, flag: &str) -> T {
+    let Some(v) = args.next() else {
+        fail(&format!("missing value for {}", flag));
+    };
+    v.parse()
+        .unwrap_or_else(|_| fail(&format!("invalid value for {}: {}", flag, v)))
+}
+
+fn list<T: std::str::FromStr>(v: &str, flag: &str) -> Vec<T> {
+    v.split(',')
+        .map(|x| {
+            x.trim()
+                .parse()
+                .unwrap_or_else(|_| fail(&format!("invalid value for {}: {}", flag, x)))
+        })
+        .collect()
+}
+
+fn activ(v: &str) -> ActivationFunc {
+    match v.to_lowercase().as_str() {
+        "relu" => ActivationFunc::ReLU,
+        "sigmoid" | "sigm" => ActivationFunc::Sigmoid,
+        "tanh" => ActivationFunc::Tanh,
+        "leaky" | "leakyrelu" => ActivationFunc::LeakyReLU,
+        "elu" => ActivationFunc::ELU,
+        "softsign" => ActivationFunc::Softsign,
+        "gaussian" => ActivationFunc::Gaussian,
+        "identity" => ActivationFunc::Identity,
+        "step" => ActivationFunc::Step,
+        _ => fail(&format!("unknown activation function: {}", v)),
+    }
+}
+
+fn parse_args() -> Args {
+    let mut a = Args {
+        stop: StopConditions::default(),
+        size: 100,
+        config: Config::default(),
+        width: 800.,
+        height: 780.,
+        threads: 0,
+        seed: None,
+        stream: 0,
+        out: "best_model.json".to_string(),
+        summary: "summary.json".to_string(),
+        hall: None,
+        checkpoint: None,
+        checkpoint_every: 10,
+        resume: None,
+        log: None,
+        lineage: None,
+        decided: None,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        if a.decided.is_none() && !RUN_OPTIONS.contains(&flag.as_str()) {
+            a.decided = Some(flag.clone());
+        }
+        match flag.as_str() {
+            "--generations" => a.stop.generations = Some(value(&mut args, &flag)),
+            "--target" => a.stop.target = Some(value(&mut args, &flag)),
+            "--patience" => a.stop.patience = Some(value(&mut args, &flag)),
+            "--time-limit" => a.stop.seconds = Some(value(&mut args, &flag)),
+            "--size" => a.size = value(&mut args, &flag),
+            "--config" => a.config = Config::load(&value::<String>(&mut args, &flag)),
+            "--brain" => {
+                let v: String = value(&mut args, &flag);
+                a.config.brain = match v.to_lowercase().as_str() {
+                    "layered" => BrainKind::Layered,
+                    "neat" => BrainKind::neat(),
+                    b if b.starts_with("neat:") => match list(&b[5..], &flag)[..] {
+                        [add_conn, add_node] => BrainKind::Neat { add_conn, add_node },
+                        _ => fail("neat takes two chances"),
+                    },
+                    _ => fail(&format!("unknown brain: {}", v)),
+                }
+            }
+            "--speciation" => a.config.speciation = true,
+            "--species-threshold" => a.config.species_threshold = Some(value(&mut args, &flag)),
+            "--stagnation" => a.config.stagnation = value(&mut args, &flag),
+            "--hlayers" => a.config.hlayers = list(&value::<String>(&mut args, &flag), &flag),
+            "--recurrent" => a.config.recurrent = true,
+            "--mut-rate" => a.config.mut_rate = value(&mut args, &flag),
+            "--mutation" => {
+                let v: String = value(&mut args, &flag);
+                let sigma = |s: &str| {
+                    s.parse()
+                        .unwrap_or_else(|_| fail("mutation sigma must be a number"))
+                };
+                a.config.mutation = match v.to_lowercase().as_str() {
+                    "reset" => Mutation::Reset,
+                    m if m.starts_with("gaussian:") => Mutation::Gaussian {
+                        sigma: sigma(&m[9..]),
+                    },
+                    m if m.starts_with("scaled:") => Mutation::Scaled {
+                        sigma: sigma(&m[7..]),
+                    },
+                    _ => fail(&format!("unknown mutation: {}", v)),
+                }
+            }
+            "--self-adaptive" => a.config.self_adaptive = true,
+            "--crossover" => {
+                let v: String = value(&mut args, &flag);
+                a.config.crossover = match v.to_lowercase().as_str() {
+                    "uniform" => Crossover::Uniform,
+                    "single" => Crossover::SinglePoint,
+                    "neuron" => Crossover::Neuron,
+                    "none" => Crossover::None,
+                    c if c.starts_with("multi:") => Crossover::MultiPoint {
+                        points: c[6..]
+                            .parse()
+                            .unwrap_or_else(|_| fail("crossover points must be a number")),
+                    },
+                    c if c.starts_with("blend:") => Crossover::Blend {
+                        alpha: c[6..]
+                            .parse()
+                            .unwrap_or_else(|_| fail("blend alpha must be a number")),
+                    },
+                    _ => fail(&format!("unknown crossover: {}", v)),
+                }
+            }
+            "--activ" => a.config.activ = activ(&value::<String>(&mut args, &flag)),
+            "--hidden-activs" => {
+                let v: String = value(&mut args, &flag);
+                a.config.hidden_activs = v
+                    .split(',')
+                    .map(|x| match x.trim().to_lowercase().as_str() {
+                        "default" => None,
+                        x => Some(activ(x)),
+                    })
+                    .collect();
+            }
+            "--out-activ" => a.config.out_activ = Some(activ(&value::<String>(&mut args, &flag))),
+            "--fitness" => {
+                let v: String = value(&mut args, &flag);
+                a.config.fitness = match v.to_lowercase().as_str() {
+                    "default" => FitnessFn::Default,
+                    "survival" => FitnessFn::Survival,
+                    "kills" => FitnessFn::Kills,
+                    "accuracy" => FitnessFn::Accuracy,
+                    w if w.starts_with("weighted:") => match list(&w[9..], &flag)[..] {
+                        [lifespan, kills, accuracy] => FitnessFn::Weighted {
+                            lifespan,
+                            kills,
+                            accuracy,
+                        },
+                        _ => fail("weighted fitness takes three weights"),
+                    },
+                    _ => fail(&format!("unknown fitness function: {}", v)),
+                }
+            }
+            "--selection" => {
+                let v: String = value(&mut args, &flag);
+                a.config.selection = match v.to_lowercase().as_str() {
+                    "roulette" => Selection::Roulette,
+                    "rank" => Selection::Rank,
+                    "sus" => Selection::StochasticUniversal,
+                    t if t.starts_with("tournament:") => Selection::Tournament {
+                        k: t[11..]
+                            .parse()
+                            .unwrap_or_else(|_| fail("tournament size must be a number")),
+                    },
+                    t if t.starts_with("truncation:") => Selection::Truncation {
+                        frac: t[11..]
+                            .parse()
+                            .unwrap_or_else(|_| fail("truncation fraction must be a number")),
+                    },
+                    _ => fail(&format!("unknown selection: {}", v)),
+                }
+            }
+            "--elitism" => a.config.elitism = value(&mut args, &flag),
+            "--elite-evals" => a.config.elite_evals = value(&mut args, &flag),
+            "--hall-of-fame" => {
+                let v: String = value(&mut args, &flag);
+                match list(&v, &flag)[..] {
+                    [kept] => a.config.hall_of_fame = kept,
+                    [kept, reinject] => {
+                        (a.config.hall_of_fame, a.config.reinject) = (kept, reinject)
+                    }
+                    _ => fail("hall of fame takes a size and a number to rejoin"),
+                }
+            }
+            "--common-scenario" => a.config.common_scenario = true,
+            "--episodes" => {
+                let v: String = value(&mut args, &flag);
+                let (n, aggregate) = v.split_once(',').unwrap_or((&v, "mean"));
+                a.config.episodes = list(n, &flag)[0];
+                a.config.aggregate = match aggregate.trim().to_lowercase().as_str() {
+                    "mean" => Aggregate::Mean,
+                    "min" => Aggregate::Min,
+                    "median" => Aggregate::Median,
+                    _ => fail(&format!("unknown aggregate: {}", aggregate)),
+                };
+                if a.config.episodes == 0 {
+                    fail("--episodes must be at least 1");
+                }
+            }
+            "--sequential-episodes" => a.config.parallel_episodes = false,
+            "--islands" => {
+                let v: String = value(&mut args, &flag);
+                let parts = v.split(',').map(str::trim).collect::<Vec<_>>();
+                let mut islands = Islands {
+                    count: list(parts[0], &flag)[0],
+                    ..Default::default()
+                };
+                if let Some(t) = parts.get(1) {
+                    islands.topology = match t.to_lowercase().as_str() {
+                        "ring" => Topology::Ring,
+                        "full" => Topology::Full,
+                        _ => fail(&format!("unknown topology: {}", t)),
+                    };
+                }
+                if let Some(i) = parts.get(2) {
+                    islands.interval = list(i, &flag)[0];
+                }
+                if let Some(m) = parts.get(3) {
+                    islands.migrants = list(m, &flag)[0];
+                }
+                if parts.len() > 4 {
+                    fail("islands take a count, topology, interval and migrants");
+                }
+                a.config.islands = Some(islands);
+            }
+            "--multi-objective" => a.config.multi_objective = true,
+            "--novelty" => {
+                let v: String = value(&mut args, &flag);
+                let mut novelty = Novelty::default();
+                match list::<f32>(&v, &flag)[..] {
+                    [weight] => novelty.weight = weight,
+                    [weight, k] => (novelty.weight, novelty.neighbours) = (weight, k as usize),
+                    [weight, k, a] => {
+                        (novelty.weight, novelty.neighbours, novelty.archived) =
+                            (weight, k as usize, a as usize)
+                    }
+                    [weight, k, a, c] => {
+                        (novelty.weight, novelty.neighbours, novelty.archived) =
+                            (weight, k as usize, a as usize);
+                        novelty.capacity = c as usize;
+                    }
+                    _ => fail("novelty takes a weight, neighbours, archived count and capacity"),
+                }
+                a.config.novelty = Some(novelty);
+            }
+            "--width" => a.width = value(&mut args, &flag),
+            "--height" => a.height = value(&mut args, &flag),
+            "--threads" => a.threads = value(&mut args, &flag),
+            "--seed" => a.seed = Some(value(&mut args, &flag)),
+            "--stream" => a.stream = value(&mut args, &flag),
+            "--out" => a.out = value(&mut args, &flag),
+            "--summary" => a.summary = value(&mut args, &flag),
+            "--hall" => a.hall = Some(value(&mut args, &flag)),
+            "--checkpoint" => a.checkpoint = Some(value(&mut args, &flag)),
+            "--checkpoint-every" => a.checkpoint_every = value(&mut args, &flag),
+            "--log" => a.log = Some(value(&mut args, &flag)),
+            "--lineage" => a.lineage = Some(value(&mut args, &flag)),
+            "--resume" => a.resume = Some(value(&mut args, &flag)),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                exit(0);
+            }
+            _ => fail(&format!("unknown argument: {}", flag)),
+        }
+    }
+    if let (Some(_), Some(flag)) = (&a.resume, &a.decided) {
+        fail(&format!(
+            "{} cannot be used with --resume, the checkpoint decides it",
+            flag
+        ));
+    }
+    if a.size == 0 {
+        fail("population size must be at least 1");
+    }
+    if a.checkpoint_every < 1 {
+        fail("--checkpoint-every must be at least 1");
+    }
+    if a.stop == StopConditions::default() {
+        a.stop.generations = Some(100);
+    }
+    a
+}
+
+fn main() {
+    let args = parse_args();
+    let (WIDTH, HEIGHT) = (args.width, args.height);
+    let mut pop = match &args.resume {
+        Some(path) => {
+            let pop = Population::load(path, None, (WIDTH, HEIGHT));
+            println!("Resuming from generation {}", pop.gen);
+            pop
+        }
+        None => {
+            let seed = args.seed.unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs())
+            });
+            println!("Seed: {}, Stream: {}", seed, args.stream);
+            Population::new(
+                args.size,
+                None,
+                args.config.clone(),
+                (WIDTH, HEIGHT),
+                Rng::new(seed, args.stream),
+            )
+        }
+    };
+    pop.set_threads(args.threads);
+    if let Some(path) = &args.log {
+        pop.log_to(path);
+    }
+    if let Some(path) = &args.lineage {
+        pop.lineage_to(path);
+    }
+    // Stop conditions given here replace the ones of a resumed checkpoint
+    pop.config.stop = args.stop;
+    pop.stopped = pop.stop_reason();
+    while pop.stopped.is_none() {
+        let gen = pop.gen;
+        while pop.gen == gen {
+            pop.update((WIDTH, HEIGHT));
+        }
+        if let Some(path) = &args.checkpoint {
+            if pop.gen % args.checkpoint_every == 0 {
+                pop.save(path);
+            }
+        }
+    }
+    if let Some(reason) = pop.stopped {
+        println!("Stopped in generation {}: {}", pop.gen, reason.describe());
+    }
+
+    if let Some(path) = &args.checkpoint {
+        pop.save(path);
+        println!("Saved checkpoint to {}", path);
+    }
+    pop.save_results(&args.out, &args.summary);
+    println!(
+        "Saved best model to {} and summary to {}",
+        args.out, args.summary
+    );
+    if let Some(path) = &args.hall {
+        pop.hall.save(path);
+        println!("Saved hall of fame to {}", path);
+    }
+}
@@ -0,0 +1,137 @@
+use nalgebra::DMatrix;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    neat::Genome,
+    nn::{ActivationFunc, Crossover, NN},
+    rng::Rng,
+};
+
+// Kind of brain new populations are made of
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum BrainKind {
+    // Fixed layers from `Config::hlayers`
+    #[default]
+    Layered,
+    // Starts with inputs wired straight to outputs. Chance per child of gaining a connection
+    // and of gaining a node.
+    Neat {
+        add_conn: f32,
+        add_node: f32,
+    },
+}
+
+impl BrainKind {
+    // NEAT with the usual growth rates
+    pub fn neat() -> Self {
+        BrainKind::Neat {
+            add_conn: 0.05,
+            add_node: 0.03,
+        }
+    }
+}
+
+// What steers a ship: a network of fixed layers, or a NEAT genome that grows its own topology.
+// Saved without a tag, so models saved before NEAT existed still load.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Brain {
+    NN(NN),
+    Neat(Genome),
+}
+
+impl Brain {
+    // Outputs for the given inputs, `memory` holds the state of recurrent networks
+    pub fn step(&self, inputs: &[f32], memory: &mut Vec<DMatrix<f32>>) -> Vec<f32> {
+        match self {
+            Brain::NN(nn) => nn.step(inputs, memory),
+            Brain::Neat(genome) => genome.feed_forward(inputs),
+        }
+    }
+
+    // For genomes `a` should be the fitter parent, the child gets its structure
+    pub fn crossover(a: &Brain, b: &Brain, crossover: Crossover, rng: &mut Rng) -> Brain {
+        match (a, b) {
+            (Brain::NN(a), Brain::NN(b)) => Brain::NN(NN::crossover(a, b, crossover, rng)),
+            (Brain::Neat(a), Brain::Neat(_)) if crossover == Crossover::None => {
+                Brain::Neat(a.clone())
+            }
+            (Brain::Neat(a), Brain::Neat(b)) => Brain::Neat(Genome::crossover(a, b, rng)),
+            _ => panic!("Brain types not same."),
+        }
+    }
+
+    pub fn distance(a: &Brain, b: &Brain) -> f32 {
+        match (a, b) {
+            (Brain::NN(a), Brain::NN(b)) => NN::distance(a, b),
+            (Brain::Neat(a), Brain::Neat(b)) => Genome::distance(a, b),
+            _ => f32::INFINITY,
+        }
+    }
+
+    pub fn out_activ(&self) -> ActivationFunc {
+        match self {
+            Brain::NN(nn) => nn.activ(nn.weights.len() - 1),
+            Brain::Neat(genome) => genome.out_activ.unwrap_or(genome.activ_func),
+        }
+    }
+
+    pub fn mut_rate(&self) -> f32 {
+        match self {
+            Brain::NN(nn) => nn.mut_rate,
+            Brain::Neat(genome) => genome.mut_rate,
+        }
+    }
+
+    pub fn set_mut_rate(&mut self, mut_rate: f32) {
+        match self {
+            Brain::NN(nn) => nn.mut_rate = mut_rate,
+            Brain::Neat(genome) => genome.mut_rate = mut_rate,
+        }
+    }
+
+    pub fn sigma(&self) -> f32 {
+        match self {
+            Brain::NN(nn) => nn.sigma,
+            Brain::Neat(genome) => genome.sigma,
+        }
+    }
+
+    pub fn set_sigma(&mut self, sigma: f32) {
+        match self {
+            Brain::NN(nn) => nn.sigma = sigma,
+            Brain::Neat(genome) => genome.sigma = sigma,
+        }
+    }
+
+    pub fn set_activ(&mut self, activ: ActivationFunc) {
+        match self {
+            Brain::NN(nn) => nn.activ_func = activ,
+            Brain::Neat(genome) => genome.activ_func = activ,
+        }
+    }
+
+    // A genome has no layers, so only the output activation applies to it
+    pub fn set_layer_activs(&mut self, layer_activs: Vec<Option<ActivationFunc>>) {
+        match self {
+            Brain::NN(nn) => nn.layer_activs = layer_activs,
+            Brain::Neat(genome) => genome.out_activ = layer_activs.last().copied().flatten(),
+        }
+    }
+
+    pub fn adapt(&mut self, rng: &mut Rng) {
+        match self {
+            Brain::NN(nn) => nn.adapt(rng),
+            Brain::Neat(genome) => genome.adapt(rng),
+        }
+    }
+
+    pub fn export(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    pub fn import(path: &str) -> Brain {
+        let json = std::fs::read_to_string(path).expect("Unable to read file");
+        serde_json::from_str(&json).unwrap()
+    }
+}
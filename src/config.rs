@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    brain::{Brain, BrainKind},
+    fitness::{Aggregate, FitnessFn},
+    islands::Islands,
+    nn::{ActivationFunc, Crossover, Mutation},
+    novelty::Novelty,
+    selection::Selection,
+    stopping::StopConditions,
+};
+
+// Settings of the genetic algorithm, saved along with checkpoints
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub brain: BrainKind,
+    // Group similar brains into species that breed among themselves, always on for NEAT
+    pub speciation: bool,
+    // Brains closer than this are in the same species, a distance that suits the kind of
+    // brain when None
+    pub species_threshold: Option<f32>,
+    // Generations a species may go without beating its best fitness before it stops
+    // getting children, 0 to never cull
+    pub stagnation: u32,
+    // Neurons in each hidden layer, zeros are skipped
+    pub hlayers: Vec<usize>,
+    // Hidden layers also see their own output from the previous frame
+    pub recurrent: bool,
+    pub mut_rate: f32,
+    pub mutation: Mutation,
+    // Every network evolves its own mutation rate and sigma, starting from the values above
+    pub self_adaptive: bool,
+    pub crossover: Crossover,
+    // Used by every layer unless overridden below
+    pub activ: ActivationFunc,
+    // Activation of each hidden layer, in the same order as `hlayers`
+    pub hidden_activs: Vec<Option<ActivationFunc>>,
+    pub out_activ: Option<ActivationFunc>,
+    pub fitness: FitnessFn,
+    pub selection: Selection,
+    // When training stops and saves its best brain, never when none is set
+    pub stop: StopConditions,
+    // Best brains of all generations kept, and how many of them rejoin every generation in
+    // place of children
+    pub hall_of_fame: usize,
+    pub reinject: usize,
+    // Every brain of a generation plays the same asteroid field
+    pub common_scenario: bool,
+    // Asteroid fields every brain plays, and how their scores are combined. Extra episodes
+    // are played alongside the shown one when parallel, or after it otherwise.
+    pub episodes: u32,
+    pub aggregate: Aggregate,
+    pub parallel_episodes: bool,
+    // Split the population into islands that breed separately
+    pub islands: Option<Islands>,
+    // Rank ships on lifespan, kills and accuracy with NSGA-II instead of on fitness
+    pub multi_objective: bool,
+    // Select on novelty of behavior, blended with fitness
+    pub novelty: Option<Novelty>,
+    // Fraction of the population copied unchanged into the next generation, at least one
+    // ship unless zero
+    pub elitism: f32,
+    // Runs averaged to pick the elites among twice as many candidates, 1 keeps the run
+    // they were picked by
+    pub elite_evals: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            brain: BrainKind::Layered,
+            speciation: false,
+            species_threshold: None,
+            stagnation: 15,
+            hlayers: vec![6, 6, 0],
+            recurrent: false,
+            mut_rate: 0.05,
+            mutation: Mutation::Reset,
+            self_adaptive: false,
+            crossover: Crossover::Uniform,
+            activ: ActivationFunc::ReLU,
+            hidden_activs: vec![None; 3],
+            out_activ: None,
+            fitness: FitnessFn::Default,
+            selection: Selection::Roulette,
+            stop: StopConditions::default(),
+            hall_of_fame: 10,
+            reinject: 0,
+            common_scenario: false,
+            episodes: 1,
+            aggregate: Aggregate::Mean,
+            parallel_episodes: true,
+            islands: None,
+            multi_objective: false,
+            novelty: None,
+            elitism: 0.05,
+            elite_evals: 1,
+        }
+    }
+}
+
+impl Config {
+    // Takes the network settings of a saved model, so a population made from them fits it
+    pub fn adopt(&mut self, brain: &Brain) {
+        match brain {
+            Brain::NN(nn) => {
+                self.brain = BrainKind::Layered;
+                self.hlayers = nn.config[1..nn.config.len() - 1]
+                    .iter()
+                    .map(|x| x - 1)
+                    .collect();
+                self.recurrent = !nn.recurrent.is_empty();
+                self.mut_rate = nn.mut_rate;
+                self.activ = nn.activ_func;
+                let hidden = nn.weights.len() - 1;
+                self.hidden_activs = (0..hidden).map(|i| nn.activ_override(i)).collect();
+                self.out_activ = nn.activ_override(hidden);
+            }
+            Brain::Neat(genome) => {
+                if self.brain == BrainKind::Layered {
+                    self.brain = BrainKind::neat();
+                }
+                self.mut_rate = genome.mut_rate;
+                self.activ = genome.activ_func;
+                self.out_activ = genome.out_activ;
+            }
+        }
+    }
+
+    // Layered networks differ by their mean weight difference, NEAT genomes by about 1 per
+    // gene only one of them has
+    pub fn species_threshold(&self) -> f32 {
+        self.species_threshold.unwrap_or(match self.brain {
+            BrainKind::Layered => 0.5,
+            BrainKind::Neat { .. } => 3.,
+        })
+    }
+
+    // Activations of every layer of a network built from `hlayers`
+    pub fn layer_activs(&self) -> Vec<Option<ActivationFunc>> {
+        self.hlayers
+            .iter()
+            .enumerate()
+            .filter(|(_, &n)| n != 0)
+            .map(|(i, _)| self.hidden_activs.get(i).copied().flatten())
+            .chain([self.out_activ])
+            .collect()
+    }
+
+    pub fn load(path: &str) -> Self {
+        let json = std::fs::read_to_string(path).expect("Unable to read file");
+        serde_json::from_str(&json).unwrap()
+    }
+}
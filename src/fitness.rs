@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+// How a ship is scored from its lifespan (in frames), asteroids destroyed and accuracy.
+// Fitness is never negative.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum FitnessFn {
+    // accuracy^2 * lifespan
+    #[default]
+    Default,
+    Survival,
+    Kills,
+    // kills * accuracy
+    Accuracy,
+    // Weights may be negative to penalize, the sum is floored at zero
+    Weighted {
+        lifespan: f32,
+        kills: f32,
+        accuracy: f32,
+    },
+}
+
+impl FitnessFn {
+    pub fn eval(&self, lifespan: u32, hits: f32, shots: u32) -> f32 {
+        // Score and shots start at 1 and 4, so accuracy is never NaN
+        let accuracy = hits / shots as f32;
+        let kills = hits - 1.;
+        match *self {
+            FitnessFn::Default => accuracy.powi(2) * lifespan as f32,
+            FitnessFn::Survival => lifespan as f32,
+            FitnessFn::Kills => kills,
+            FitnessFn::Accuracy => kills * accuracy,
+            FitnessFn::Weighted {
+                lifespan: a,
+                kills: b,
+                accuracy: c,
+            } => (a * lifespan as f32 + b * kills + c * accuracy).max(0.),
+        }
+    }
+}
+
+// How the scores of a brain over several episodes are combined
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Aggregate {
+    #[default]
+    Mean,
+    // Rewards brains that never do badly
+    Min,
+    // Ignores a single lucky or unlucky episode
+    Median,
+}
+
+impl Aggregate {
+    pub fn apply(&self, mut values: Vec<f32>) -> f32 {
+        match self {
+            Aggregate::Mean => values.iter().sum::<f32>() / values.len() as f32,
+            Aggregate::Min => values.into_iter().fold(f32::INFINITY, f32::min),
+            Aggregate::Median => {
+                values.sort_by(|a, b| a.total_cmp(b));
+                let mid = values.len() / 2;
+                if values.len().is_multiple_of(2) {
+                    (values[mid - 1] + values[mid]) * 0.5
+                } else {
+                    values[mid]
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fitness_is_never_negative() {
+        let weighted = FitnessFn::Weighted {
+            lifespan: -0.1,
+            kills: 20.,
+            accuracy: -5.,
+        };
+        for f in [
+            FitnessFn::Default,
+            FitnessFn::Survival,
+            FitnessFn::Kills,
+            FitnessFn::Accuracy,
+            weighted,
+        ] {
+            for (lifespan, hits, shots) in [(0, 1., 4), (1000, 1., 4), (600, 8., 30)] {
+                assert!(f.eval(lifespan, hits, shots) >= 0., "{:?}", f);
+            }
+        }
+        assert_eq!(weighted.eval(100, 3., 10), 20. * 2. - 10. - 1.5);
+    }
+
+    #[test]
+    fn aggregates() {
+        let runs = vec![4., 1., 10., 3.];
+        assert_eq!(Aggregate::Mean.apply(runs.clone()), 4.5);
+        assert_eq!(Aggregate::Min.apply(runs.clone()), 1.);
+        assert_eq!(Aggregate::Median.apply(runs), 3.5);
+        assert_eq!(Aggregate::Median.apply(vec![2., 9., 5.]), 5.);
+        assert_eq!(Aggregate::Mean.apply(vec![7.]), 7.);
+    }
+}
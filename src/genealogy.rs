@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::stats::Log;
+
+// Where a brain came from
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Record {
+    pub id: u64,
+    // Fitter parent first, none for brains of the first generation
+    pub parents: Vec<u64>,
+    // Generations completed when it was made
+    pub gen: i32,
+    // Genes changed by mutation when it was made, and along the line of fitter parents back
+    // to the first generation
+    pub mutations: u32,
+    #[serde(default)]
+    pub line: u32,
+    // Best fitness it scored in any generation
+    pub fitness: f32,
+}
+
+// Generations of ancestors kept for every living brain and brain in the hall of fame
+pub const ANCESTRY: usize = 8;
+
+// Ids of every brain the population made and who their parents were. Elites keep their
+// id. Records are dropped once they are more than `ANCESTRY` generations back from every
+// living brain and brain in the hall of fame.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Genealogy {
+    next_id: u64,
+    pub records: BTreeMap<u64, Record>,
+}
+
+impl Genealogy {
+    pub fn birth(&mut self, parents: Vec<u64>, gen: i32, mutations: u32) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        let line = parents
+            .first()
+            .and_then(|&p| self.get(p))
+            .map_or(0, |r| r.line);
+        self.records.insert(
+            id,
+            Record {
+                id,
+                parents,
+                gen,
+                mutations,
+                line: line + mutations,
+                fitness: 0.,
+            },
+        );
+        id
+    }
+
+    pub fn get(&self, id: u64) -> Option<&Record> {
+        self.records.get(&id)
+    }
+
+    pub fn score(&mut self, id: u64, fitness: f32) {
+        if let Some(record) = self.records.get_mut(&id) {
+            record.fitness = record.fitness.max(fitness);
+        }
+    }
+
+    // Ancestors of a brain up to `depth` generations back, depth first with how far back
+    // each is, the brain itself first
+    pub fn tree(&self, id: u64, depth: usize) -> Vec<(usize, &Record)> {
+        let mut tree = vec![];
+        let mut stack = vec![(0, id)];
+        while let Some((d, id)) = stack.pop() {
+            if let Some(record) = self.get(id) {
+                tree.push((d, record));
+                if d < depth {
+                    stack.extend(record.parents.iter().rev().map(|&p| (d + 1, p)));
+                }
+            }
+        }
+        tree
+    }
+
+    pub fn prune(&mut self, living: impl IntoIterator<Item = u64>) {
+        // Fewest generations back any kept record is from a living brain
+        let mut keep = BTreeMap::new();
+        let mut stack = living.into_iter().map(|id| (0, id)).collect::<Vec<_>>();
+        while let Some((d, id)) = stack.pop() {
+            if keep.get(&id).is_some_and(|&k| k <= d) {
+                continue;
+            }
+            keep.insert(id, d);
+            match self.records.get(&id) {
+                Some(record) if d < ANCESTRY => {
+                    stack.extend(record.parents.iter().map(|&p| (d + 1, p)))
+                }
+                _ => {}
+            }
+        }
+        self.records.retain(|id, _| keep.contains_key(id));
+    }
+}
+
+// Writes every brain of every generation with its parents and score
+pub struct LineageLog(Log);
+
+#[derive(Serialize)]
+struct Line<'a> {
+    gen: i32,
+    id: u64,
+    parents: &'a [u64],
+    born: i32,
+    mutations: u32,
+    score: f32,
+}
+
+impl LineageLog {
+    pub fn create(path: &str) -> Self {
+        Self(Log::create(path, "gen,id,parents,born,mutations,score"))
+    }
+
+    // `scores` are the ids and fitness of the brains of generation `gen`
+    pub fn write(&mut self, gen: i32, scores: &[(u64, f32)], genealogy: &Genealogy) {
+        for &(id, score) in scores {
+            let Some(record) = genealogy.get(id) else {
+                continue;
+            };
+            let line = Line {
+                gen,
+                id,
+                parents: &record.parents,
+                born: record.gen,
+                mutations: record.mutations,
+                score,
+            };
+            self.0.write(&line, |l| {
+                let parents = l.parents.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+                format!(
+                    "{},{},{},{},{},{}",
+                    l.gen,
+                    l.id,
+                    parents.join(";"),
+                    l.born,
+                    l.mutations,
+                    l.score
+                )
+            });
+        }
+        self.0.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A single line of descent, one brain per generation
+    fn line(genealogy: &mut Genealogy, length: usize) -> Vec<u64> {
+        let mut ids = vec![genealogy.birth(vec![], 0, 1)];
+        for gen in 1..length {
+            let parent = *ids.last().unwrap();
+            ids.push(genealogy.birth(vec![parent], gen as i32, 2));
+        }
+        ids
+    }
+
+    #[test]
+    fn prune_keeps_recent_ancestors() {
+        let mut genealogy = Genealogy::default();
+        let ids = line(&mut genealogy, 20);
+        let other = line(&mut genealogy, 3);
+        genealogy.prune([ids[19]]);
+        let kept = genealogy.records.keys().copied().collect::<Vec<_>>();
+        assert_eq!(kept, ids[19 - ANCESTRY..].to_vec());
+        assert!(other.iter().all(|&id| genealogy.get(id).is_none()));
+        // Line totals survive pruning
+        assert_eq!(genealogy.get(ids[19]).unwrap().line, 1 + 2 * 19);
+    }
+
+    #[test]
+    fn prune_uses_the_closest_path() {
+        let mut genealogy = Genealogy::default();
+        let ids = line(&mut genealogy, 12);
+        // A child of a recent brain and of the first one
+        let child = genealogy.birth(vec![ids[11], ids[0]], 12, 0);
+        genealogy.prune([child]);
+        assert!(genealogy.get(ids[0]).is_some());
+        assert!(genealogy.get(ids[11 - ANCESTRY]).is_none());
+        assert!(genealogy.get(ids[12 - ANCESTRY]).is_some());
+    }
+
+    #[test]
+    fn tree_goes_depth_first_to_the_given_depth() {
+        let mut genealogy = Genealogy::default();
+        let a = genealogy.birth(vec![], 0, 0);
+        let b = genealogy.birth(vec![], 0, 0);
+        let c = genealogy.birth(vec![a, b], 1, 0);
+        let d = genealogy.birth(vec![c], 2, 0);
+        let tree = genealogy.tree(d, 2);
+        let tree = tree
+            .iter()
+            .map(|(depth, r)| (*depth, r.id))
+            .collect::<Vec<_>>();
+        assert_eq!(tree, vec![(0, d), (1, c), (2, a), (2, b)]);
+        assert_eq!(genealogy.tree(d, 1).len(), 2);
+    }
+}
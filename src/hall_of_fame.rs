@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{brain::Brain, world::World};
+
+// Best brain seen so far, with how it did in its best generation
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub brain: Brain,
+    // Id in the genealogy of the population
+    #[serde(default)]
+    pub id: u64,
+    pub gen: i32,
+    pub fitness: f32,
+    pub lifespan: f32,
+    pub kills: f32,
+    pub accuracy: f32,
+}
+
+// The fittest brains of all generations, fittest first. Elites are copied unchanged with
+// their genealogy id, so a brain already in the hall keeps a single entry with its best run.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct HallOfFame {
+    pub entries: Vec<Entry>,
+}
+
+impl HallOfFame {
+    pub fn add(&mut self, worlds: &[World], gen: i32, size: usize) {
+        if size == 0 {
+            self.entries.clear();
+            return;
+        }
+        for world in worlds {
+            if self.entries.len() >= size
+                && self
+                    .entries
+                    .last()
+                    .is_some_and(|e| e.fitness >= world.fitness)
+            {
+                continue;
+            }
+            let [lifespan, kills, accuracy] = world.objectives();
+            let entry = Entry {
+                brain: world.see_brain().clone(),
+                id: world.id,
+                gen,
+                fitness: world.fitness,
+                lifespan,
+                kills,
+                accuracy,
+            };
+            match self.entries.iter_mut().find(|e| e.id == world.id) {
+                Some(e) if e.fitness < world.fitness => *e = entry,
+                Some(_) => {}
+                None => self.entries.push(entry),
+            }
+            self.entries.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+            self.entries.truncate(size);
+        }
+    }
+
+    pub fn save(&self, path: &str) {
+        let json = serde_json::to_string(self).unwrap();
+        std::fs::write(path, json).expect("Unable to write file");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{brain::Brain, player::Player, rng::Rng};
+
+    fn world(id: u64, fitness: f32, rng: &mut Rng) -> World {
+        let brain = Player::new_genome(0.05, Default::default(), rng);
+        let mut world = World::simulate(Brain::Neat(brain), (800., 600.), 0);
+        (world.id, world.fitness) = (id, fitness);
+        world
+    }
+
+    #[test]
+    fn keeps_one_entry_per_brain_with_its_best_generation() {
+        let mut rng = Rng::new(2, 0);
+        let mut hall = HallOfFame::default();
+        let first = [world(1, 5., &mut rng), world(2, 3., &mut rng)];
+        hall.add(&first, 1, 3);
+        let second = [
+            world(1, 8., &mut rng),
+            world(3, 4., &mut rng),
+            world(4, 1., &mut rng),
+        ];
+        hall.add(&second, 2, 3);
+        let entries = hall.entries.iter().map(|e| (e.id, e.gen, e.fitness));
+        assert_eq!(
+            entries.collect::<Vec<_>>(),
+            vec![(1, 2, 8.), (3, 2, 4.), (2, 1, 3.)]
+        );
+        hall.add(&second, 3, 0);
+        assert!(hall.entries.is_empty());
+    }
+}
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+// Which islands send their best ships to which
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Topology {
+    // Each island to the next one
+    #[default]
+    Ring,
+    // Each island to all the others
+    Full,
+}
+
+// Sub-populations that breed on their own and swap their best ships now and then, so they
+// can wander off to different solutions instead of all converging on the first good one
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Islands {
+    pub count: usize,
+    pub topology: Topology,
+    // Generations between migrations, 0 to never migrate
+    pub interval: u32,
+    // Best ships each island sends to each of its neighbours
+    pub migrants: usize,
+}
+
+impl Default for Islands {
+    fn default() -> Self {
+        Self {
+            count: 4,
+            topology: Topology::Ring,
+            interval: 10,
+            migrants: 2,
+        }
+    }
+}
+
+impl Islands {
+    // Ships living on an island, ships are dealt to islands in turn
+    pub fn share(&self, size: usize, island: usize) -> usize {
+        size / self.count + (island < size % self.count) as usize
+    }
+
+    // Islands sending ships to the given one
+    pub fn sources(&self, island: usize) -> Vec<usize> {
+        match self.topology {
+            Topology::Ring => vec![(island + self.count - 1) % self.count],
+            Topology::Full => (0..self.count).collect(),
+        }
+        .into_iter()
+        .filter(|&i| i != island)
+        .collect()
+    }
+}
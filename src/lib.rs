@@ -0,0 +1,23 @@
+#![allow(non_snake_case)]
+
+pub mod asteroids;
+pub mod brain;
+pub mod config;
+pub mod fitness;
+pub mod genealogy;
+pub mod hall_of_fame;
+pub mod islands;
+pub mod neat;
+pub mod nn;
+pub mod novelty;
+pub mod pareto;
+pub mod player;
+pub mod population;
+#[cfg(feature = "render")]
+mod render;
+pub mod rng;
+pub mod selection;
+pub mod species;
+pub mod stats;
+pub mod stopping;
+pub mod world;
@@ -1,22 +1,26 @@
 #![windows_subsystem = "windows"]
+#![allow(non_snake_case)]
 
-mod asteroids;
-mod nn;
-mod player;
-mod population;
 mod skins;
-mod world;
-
-use nn::{ActivationFunc, NN};
 
+use genetic::{
+    brain::{Brain, BrainKind},
+    config::Config,
+    fitness::{Aggregate, FitnessFn},
+    islands::{Islands, Topology},
+    nn::{ActivationFunc, Crossover, Mutation},
+    novelty::Novelty,
+    population::{AutoSwitch, Population},
+    rng::Rng,
+    selection::Selection,
+    world::World,
+};
 use macroquad::{
     prelude::*,
     ui::{hash, root_ui, widgets},
 };
-use population::{AutoSwitch, Population};
 #[cfg(not(target_arch = "wasm32"))]
 use tinyfiledialogs::{open_file_dialog, save_file_dialog};
-use world::World;
 
 fn window_conf() -> Conf {
     Conf {
@@ -25,9 +29,18 @@ fn window_conf() -> Conf {
         ..Default::default()
     }
 }
+
+// Hidden layers fit the combo boxes, more or larger ones from the command line are only
+// shown so restarts keep them
+fn editable(hlayers: &[usize]) -> bool {
+    hlayers.len() <= 3 && hlayers.iter().all(|&n| n <= 16)
+}
+
 #[macroquad::main(window_conf)]
 async fn main() {
+    // Only used for visual effects, the simulation draws from `rng`
     rand::srand(macroquad::miniquad::date::now() as _);
+    let mut rng = Rng::new(macroquad::miniquad::date::now() as _, 0);
 
     let pause = Texture2D::from_file_with_format(include_bytes!("../assets/pause.png"), None);
     let play = Texture2D::from_file_with_format(include_bytes!("../assets/play.png"), None);
@@ -53,6 +66,18 @@ async fn main() {
         offset: vec2((th + WIDTH) / SWIDTH, -((th + HEIGHT) * 0.5) / SHEIGHT),
         ..Default::default()
     };
+    // Network and fitness chart sharing the lower panel
+    let panel = (SHEIGHT - 3. * th) * 0.5;
+    let splitnetcam = Camera2D {
+        zoom: vec2(2. / SWIDTH, 2. / SHEIGHT),
+        offset: netcam.offset + vec2(0., 0.4 * panel / SHEIGHT),
+        ..Default::default()
+    };
+    let chartcam = Camera2D {
+        zoom: vec2(2. / SWIDTH, 2. / SHEIGHT),
+        offset: netcam.offset - vec2(0., 0.6 * panel / SHEIGHT),
+        ..Default::default()
+    };
     let statcam = Camera2D {
         zoom: vec2(2. / SWIDTH, 2. / SHEIGHT),
         offset: vec2((th + WIDTH) / SWIDTH, ((th + HEIGHT) * 0.5) / SHEIGHT),
@@ -62,15 +87,25 @@ async fn main() {
     let mut speedup = 1;
     let mut paused = false;
     let mut bias = false;
+    let mut chart = false;
+    let mut log_scale = false;
+    // Pareto front in place of the fitness chart
+    let mut front = false;
+    let mut settings = false;
+    let mut hall = false;
+    let mut lineage = false;
+    // Brain whose ancestry is shown, the tracked ship's when None
+    let mut lineage_focus = None;
+    // Where the results of the last stopped run went
+    let mut results: Option<String> = None;
     let mut human = false;
     let mut size: u32 = 100;
-    let mut world: World = World::new(None, None, None, (WIDTH, HEIGHT));
+    let mut world: World = World::new(None, None, None, (WIDTH, HEIGHT), &mut rng);
 
-    let mut hlayers: Vec<usize> = vec![6, 6, 0];
-    let mut prev_hlayers = hlayers.clone();
+    let mut config = Config::default();
+    let mut prev_hlayers = config.hlayers.clone();
 
-    let mut mut_rate = 0.05;
-    let mut prev_mut_rate = 0.05;
+    let mut prev_mut_rate = config.mut_rate;
 
     let mut activ: usize = 0;
     let mut prev_activ: usize = 0;
@@ -78,19 +113,44 @@ async fn main() {
         ActivationFunc::ReLU,
         ActivationFunc::Sigmoid,
         ActivationFunc::Tanh,
+        ActivationFunc::LeakyReLU,
+        ActivationFunc::ELU,
+        ActivationFunc::Softsign,
+        ActivationFunc::Gaussian,
+        ActivationFunc::Identity,
+        ActivationFunc::Step,
+    ];
+    let activ_names = [
+        "ReLU", "Sigm", "Tanh", "Leaky", "ELU", "Soft", "Gauss", "Ident", "Step",
     ];
     let mut auto_switch = Some(AutoSwitch::BestAlive);
 
     let mut pop = Population::new(
         size as usize,
         auto_switch,
-        hlayers.clone(),
-        mut_rate,
-        activs[activ],
+        config.clone(),
         (WIDTH, HEIGHT),
+        rng.fork(),
     );
 
     let ui_thick = 34.;
+    let settings_width = WIDTH * 0.45;
+    let fitness_fns = ["Default", "Survival", "Kills", "Accuracy", "Weighted"];
+    let layer_activ_names = [
+        "Default", "ReLU", "Sigm", "Tanh", "Leaky", "ELU", "Soft", "Gauss", "Ident", "Step",
+    ];
+    let mutations = ["Reset", "Gaussian", "Scaled"];
+    let crossovers = [
+        "Uniform",
+        "Single Point",
+        "Multi Point",
+        "Neuron",
+        "Blend",
+        "None",
+    ];
+    let topologies = ["Ring", "Full"];
+    let aggregates = ["Mean", "Min", "Median"];
+    let selections = ["Roulette", "Tournament", "Rank", "SUS", "Truncation"];
     let nums = &[
         "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15", "16",
     ];
@@ -106,10 +166,31 @@ async fn main() {
         clear_background(BLACK);
         set_camera(&gamecam);
         if !paused {
+            let stopped = pop.stopped.is_some();
             for _ in 0..speedup {
                 if !human {
-                    pop.update((WIDTH, HEIGHT))
+                    pop.update((WIDTH, HEIGHT));
+                    // Pause once a stop condition is met, playing on does not stop again
+                    if !stopped && pop.stopped.is_some() {
+                        paused = true;
+                        results = None;
+                        // The summary goes next to the model, named after it
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if let Some(path) = save_file_dialog("Save Best Model", "best_model.json") {
+                            let summary =
+                                std::path::Path::new(&path).with_extension("summary.json");
+                            pop.save_results(&path, &summary.to_string_lossy());
+                            results = Some(path);
+                        }
+                        break;
+                    }
                 } else if !world.over {
+                    world.player.input = [
+                        is_key_down(KeyCode::Right),
+                        is_key_down(KeyCode::Left),
+                        is_key_down(KeyCode::Up),
+                        is_key_down(KeyCode::Space),
+                    ];
                     world.update((WIDTH, HEIGHT))
                 };
             }
@@ -121,6 +202,23 @@ async fn main() {
             pop.draw((WIDTH, HEIGHT, SWIDTH, SHEIGHT));
         }
         draw_rectangle_lines(-WIDTH * 0.5, -HEIGHT * 0.5, WIDTH, HEIGHT, 2., WHITE);
+        if settings || hall || lineage {
+            draw_rectangle(
+                -WIDTH * 0.5 + th,
+                -HEIGHT * 0.5 + th,
+                settings_width,
+                HEIGHT - 2. * th,
+                BLACK,
+            );
+            draw_rectangle_lines(
+                -WIDTH * 0.5 + th,
+                -HEIGHT * 0.5 + th,
+                settings_width,
+                HEIGHT - 2. * th,
+                2.,
+                WHITE,
+            );
+        }
         draw_rectangle_lines(
             WIDTH * 0.5 + th,
             -HEIGHT * 0.5,
@@ -138,12 +236,23 @@ async fn main() {
             WHITE,
         );
 
-        set_camera(&netcam);
-        pop.worlds[pop.track].player.draw_brain(
-            SWIDTH - WIDTH - 3. * th,
-            (SHEIGHT - 3. * th) * 0.5,
-            bias,
-        );
+        if chart {
+            set_camera(&splitnetcam);
+            pop.worlds[pop.track]
+                .player
+                .draw_brain(SWIDTH - WIDTH - 3. * th, panel * 0.6, bias);
+            set_camera(&chartcam);
+            if front && config.multi_objective {
+                pop.draw_front(SWIDTH - WIDTH - 3. * th, panel * 0.4);
+            } else {
+                pop.draw_history(SWIDTH - WIDTH - 3. * th, panel * 0.4, log_scale);
+            }
+        } else {
+            set_camera(&netcam);
+            pop.worlds[pop.track]
+                .player
+                .draw_brain(SWIDTH - WIDTH - 3. * th, panel, bias);
+        }
         set_camera(&statcam);
         let w = if human {
             &world
@@ -162,6 +271,9 @@ async fn main() {
             }),
         );
         if !pop.focus
+            && !settings
+            && !hall
+            && !lineage
             && is_mouse_button_pressed(MouseButton::Left)
             && mouse_position().0 < WIDTH + th
         {
@@ -169,6 +281,9 @@ async fn main() {
             pop.change_track(vec2(x - th - WIDTH * 0.5, y - th - HEIGHT * 0.5));
         }
 
+        // Brain to put alone in the arena, with its id, the hall of fame and the genealogy to
+        // keep
+        let mut load = None;
         let ui_width = SWIDTH - WIDTH - 3. * th + 1.;
         let ui_height = (SHEIGHT - 3. * th) * 0.5;
         root_ui().window(
@@ -188,32 +303,8 @@ async fn main() {
                         if widgets::Button::new("Load Model").ui(ui) {
                             #[cfg(not(target_arch = "wasm32"))]
                             if let Some(path) = open_file_dialog("Load Model", "model.json", None) {
-                                let brain = NN::import(&path);
-                                size = 1;
-                                hlayers = brain
-                                    .config
-                                    .iter()
-                                    .take(brain.config.len() - 1)
-                                    .skip(1)
-                                    .map(|x| x - 1)
-                                    .collect::<Vec<_>>();
-                                hlayers.resize(3, 0);
-                                mut_rate = brain.mut_rate;
-                                activ = activs.iter().position(|&x| x == brain.activ_func).unwrap();
-
-                                prev_hlayers = hlayers.clone();
-                                prev_mut_rate = mut_rate;
-                                prev_activ = activ;
-
-                                pop = Population::new(
-                                    size as usize,
-                                    auto_switch,
-                                    hlayers.clone(),
-                                    mut_rate,
-                                    activs[activ],
-                                    (WIDTH, HEIGHT),
-                                );
-                                pop.worlds[0] = World::simulate(brain, (WIDTH, HEIGHT));
+                                let brain = Brain::import(&path);
+                                load = Some((brain, None, Default::default(), Default::default()));
                             }
                         }
                         ui.same_line(0.);
@@ -280,15 +371,14 @@ async fn main() {
                             || is_key_pressed(KeyCode::R)
                         {
                             if human {
-                                world = World::new(None, None, None, (WIDTH, HEIGHT));
+                                world = World::new(None, None, None, (WIDTH, HEIGHT), &mut rng);
                             } else {
                                 pop = Population::new(
                                     size as usize,
                                     auto_switch,
-                                    hlayers.clone(),
-                                    mut_rate,
-                                    activs[activ],
+                                    config.clone(),
                                     (WIDTH, HEIGHT),
+                                    rng.fork(),
                                 );
                             }
                         };
@@ -312,7 +402,7 @@ async fn main() {
                         auto_switch = Some(AutoSwitch::Best);
                         pop.auto_switch = auto_switch;
                     }
-                    if ui.button(None, "LastGen #1") {
+                    if pop.prev_best.is_some() && ui.button(None, "LastGen #1") {
                         pop.track_prev_best();
                         auto_switch = None;
                         pop.auto_switch = auto_switch;
@@ -350,6 +440,87 @@ async fn main() {
                     hash!(),
                     vec2(ui_width * 0.2, ui_height * 0.85 - 2. * th - 2. * ui_thick),
                 )
+                .position(vec2(ui_width * 0.38 - th, ui_height * 0.15 + ui_thick + th))
+                .ui(ui, |ui| {
+                    ui.label(None, "Fitness Chart:");
+                    if ui.button(None, if chart { "Hide Chart" } else { "Show Chart" })
+                        || is_key_pressed(KeyCode::G)
+                    {
+                        chart = !chart;
+                    }
+                    if ui.button(None, if log_scale { "Log:ON " } else { "Log:OFF" })
+                        || is_key_pressed(KeyCode::L)
+                    {
+                        log_scale = !log_scale;
+                    }
+                    if config.multi_objective
+                        && ui.button(None, if front { "Pareto:ON " } else { "Pareto:OFF" })
+                    {
+                        front = !front;
+                    }
+                    ui.label(None, " ");
+                    ui.label(None, "Checkpoint:");
+                    if ui.button(None, "Save All") {
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if let Some(path) = save_file_dialog("Save Checkpoint", "checkpoint.json") {
+                            pop.save(&path);
+                        }
+                    }
+                    if ui.button(None, "Load All") {
+                        #[cfg(not(target_arch = "wasm32"))]
+                        if let Some(path) =
+                            open_file_dialog("Load Checkpoint", "checkpoint.json", None)
+                        {
+                            pop = Population::load(&path, auto_switch, (WIDTH, HEIGHT));
+                            size = pop.worlds.len() as u32;
+                            config = pop.config.clone();
+                            if editable(&config.hlayers) {
+                                config.hidden_activs.resize(3, None);
+                            }
+                            activ = activs.iter().position(|&x| x == config.activ).unwrap();
+
+                            prev_hlayers = config.hlayers.clone();
+                            prev_mut_rate = config.mut_rate;
+                            prev_activ = activ;
+                            human = false;
+                        }
+                    }
+                    ui.label(None, " ");
+                    if ui.button(None, if settings { "Close GA" } else { "GA Settings" }) {
+                        settings = !settings;
+                        hall = false;
+                        lineage = false;
+                    }
+                    if ui.button(None, if hall { "Close Hall" } else { "Hall of Fame" }) {
+                        hall = !hall;
+                        settings = false;
+                        lineage = false;
+                    }
+                    if ui.button(None, if lineage { "Close Lineage" } else { "Lineage" }) {
+                        lineage = !lineage;
+                        lineage_focus = None;
+                        settings = false;
+                        hall = false;
+                    }
+                    if !pop.species.is_empty() {
+                        ui.label(None, &format!("Species: {}", pop.species.len()));
+                    }
+                    if pop.config.islands.is_some() {
+                        let island = pop.worlds[pop.track].island;
+                        ui.label(None, &format!("Island: {}", island + 1));
+                    }
+                    if let Some(reason) = pop.stopped {
+                        ui.label(None, &format!("Stopped: {}", reason.describe()));
+                        match &results {
+                            Some(path) => ui.label(None, &format!("Saved to {}", path)),
+                            None => ui.label(None, "Results not saved"),
+                        }
+                    }
+                });
+                widgets::Group::new(
+                    hash!(),
+                    vec2(ui_width * 0.2, ui_height * 0.85 - 2. * th - 2. * ui_thick),
+                )
                 .position(vec2(ui_width * 0.6 - th, ui_height * 0.15 + ui_thick + th))
                 .ui(ui, |ui| {
                     ui.label(None, " ");
@@ -364,53 +535,587 @@ async fn main() {
                     ) {
                         human = !human;
                         if human {
-                            world = World::new(None, None, None, (WIDTH, HEIGHT));
+                            world = World::new(None, None, None, (WIDTH, HEIGHT), &mut rng);
                         } else {
                             pop = Population::new(
                                 size as usize,
                                 auto_switch,
-                                hlayers.clone(),
-                                mut_rate,
-                                activs[activ],
+                                config.clone(),
                                 (WIDTH, HEIGHT),
+                                rng.fork(),
                             );
                         }
                     }
                     ui.pop_skin();
                     ui.label(None, "Mutation Rate");
-                    ui.drag(hash!(), "«Drag»", Some((0., 1.)), &mut mut_rate);
-                    if prev_mut_rate != mut_rate {
-                        pop.change_mut(mut_rate);
-                        prev_mut_rate = mut_rate;
+                    ui.drag(hash!(), "«Drag»", Some((0., 1.)), &mut config.mut_rate);
+                    if prev_mut_rate != config.mut_rate {
+                        pop.change_mut(config.mut_rate);
+                        prev_mut_rate = config.mut_rate;
                     }
                     ui.label(None, "Activation Func");
-                    ui.combo_box(hash!(), "«Select»", &["ReLU", "Sigm", "Tanh"], &mut activ);
+                    ui.combo_box(hash!(), "«Select»", &activ_names, &mut activ);
                     if prev_activ != activ {
-                        pop.change_activ(activs[activ]);
+                        config.activ = activs[activ];
+                        pop.change_activ(config.activ);
                         prev_activ = activ;
                     }
                     ui.label(None, " ");
                     ui.label(None, "Hidden Layers");
                     ui.label(None, "Neurons Config");
 
-                    ui.combo_box(hash!(), "Layer 1", nums, &mut hlayers[0]);
-                    ui.combo_box(hash!(), "Layer 2", nums, &mut hlayers[1]);
-                    ui.combo_box(hash!(), "Layer 3", nums, &mut hlayers[2]);
-                    if prev_hlayers != hlayers {
+                    if editable(&config.hlayers) {
+                        let mut hlayers = config.hlayers.clone();
+                        hlayers.resize(3, 0);
+                        ui.combo_box(hash!(), "Layer 1", nums, &mut hlayers[0]);
+                        ui.combo_box(hash!(), "Layer 2", nums, &mut hlayers[1]);
+                        ui.combo_box(hash!(), "Layer 3", nums, &mut hlayers[2]);
+                        // Padding with empty layers alone is no change
+                        if hlayers
+                            .iter()
+                            .zip(config.hlayers.iter().chain([&0; 3]))
+                            .any(|(a, b)| a != b)
+                        {
+                            config.hlayers = hlayers;
+                        }
+                    } else {
+                        let hlayers = config.hlayers.iter().map(|n| n.to_string());
+                        ui.label(None, &hlayers.collect::<Vec<_>>().join(", "));
+                    }
+                    if prev_hlayers != config.hlayers {
                         pop = Population::new(
                             size as usize,
                             auto_switch,
-                            hlayers.clone(),
-                            mut_rate,
-                            activs[activ],
+                            config.clone(),
                             (WIDTH, HEIGHT),
+                            rng.fork(),
                         );
-                        prev_hlayers = hlayers.clone();
+                        prev_hlayers = config.hlayers.clone();
                     }
                 });
                 ui.pop_skin();
             },
         );
+        if settings {
+            root_ui().window(
+                hash!(),
+                vec2(2. * th, 2. * th),
+                vec2(settings_width, HEIGHT - 2. * th),
+                |ui| {
+                    ui.push_skin(&skin2);
+                    ui.label(None, "Brain (restarts)");
+                    let mut kind = match config.brain {
+                        BrainKind::Layered => 0,
+                        BrainKind::Neat { .. } => 1,
+                    };
+                    ui.combo_box(hash!(), "«Select»", &["Layered", "NEAT"], &mut kind);
+                    config.brain = match (kind, config.brain) {
+                        (0, _) => BrainKind::Layered,
+                        (_, b @ BrainKind::Neat { .. }) => b,
+                        _ => BrainKind::neat(),
+                    };
+                    if let BrainKind::Neat { add_conn, add_node } = &mut config.brain {
+                        ui.drag(hash!(), "Add Connection", Some((0., 1.)), add_conn);
+                        ui.drag(hash!(), "Add Node", Some((0., 1.)), add_node);
+                    }
+                    if std::mem::discriminant(&config.brain)
+                        != std::mem::discriminant(&pop.config.brain)
+                    {
+                        pop = Population::new(
+                            size as usize,
+                            auto_switch,
+                            config.clone(),
+                            (WIDTH, HEIGHT),
+                            rng.fork(),
+                        );
+                    }
+                    ui.label(None, " ");
+                    if config.brain == BrainKind::Layered {
+                        ui.checkbox(hash!(), "Speciation", &mut config.speciation);
+                    } else {
+                        ui.label(None, "Speciation (always on for NEAT)");
+                    }
+                    if config.speciation || config.brain != BrainKind::Layered {
+                        let mut threshold = config.species_threshold();
+                        ui.drag(
+                            hash!(),
+                            "Species Distance",
+                            Some((0.01, 10.)),
+                            &mut threshold,
+                        );
+                        if threshold != config.species_threshold() {
+                            config.species_threshold = Some(threshold);
+                        }
+                        ui.drag(
+                            hash!(),
+                            "Stagnation",
+                            Some((0, 100)),
+                            &mut config.stagnation,
+                        );
+                    }
+                    ui.label(None, " ");
+                    let mut islands = config.islands.is_some();
+                    ui.checkbox(hash!(), "Islands (restarts)", &mut islands);
+                    config.islands = match (islands, config.islands) {
+                        (true, i @ Some(_)) => i,
+                        (true, None) => Some(Islands::default()),
+                        (false, _) => None,
+                    };
+                    if let Some(islands) = &mut config.islands {
+                        let mut count = islands.count as u32;
+                        ui.drag(hash!(), "Count", Some((2, 16)), &mut count);
+                        islands.count = count as usize;
+                        let mut topology = match islands.topology {
+                            Topology::Ring => 0,
+                            Topology::Full => 1,
+                        };
+                        ui.combo_box(hash!(), "Migration", &topologies, &mut topology);
+                        islands.topology = match topology {
+                            0 => Topology::Ring,
+                            _ => Topology::Full,
+                        };
+                        ui.drag(hash!(), "Interval", Some((0, 100)), &mut islands.interval);
+                        let mut migrants = islands.migrants as u32;
+                        ui.drag(hash!(), "Migrants", Some((0, 20)), &mut migrants);
+                        islands.migrants = migrants as usize;
+                    }
+                    if config.islands.map(|i| i.count) != pop.config.islands.map(|i| i.count) {
+                        pop = Population::new(
+                            size as usize,
+                            auto_switch,
+                            config.clone(),
+                            (WIDTH, HEIGHT),
+                            rng.fork(),
+                        );
+                    }
+                    ui.label(None, " ");
+                    ui.label(None, "Fitness Function");
+                    let mut fitness = match config.fitness {
+                        FitnessFn::Default => 0,
+                        FitnessFn::Survival => 1,
+                        FitnessFn::Kills => 2,
+                        FitnessFn::Accuracy => 3,
+                        FitnessFn::Weighted { .. } => 4,
+                    };
+                    ui.combo_box(hash!(), "«Select»", &fitness_fns, &mut fitness);
+                    config.fitness = match (fitness, config.fitness) {
+                        (0, _) => FitnessFn::Default,
+                        (1, _) => FitnessFn::Survival,
+                        (2, _) => FitnessFn::Kills,
+                        (3, _) => FitnessFn::Accuracy,
+                        (_, f @ FitnessFn::Weighted { .. }) => f,
+                        _ => FitnessFn::Weighted {
+                            lifespan: 1.,
+                            kills: 100.,
+                            accuracy: 0.,
+                        },
+                    };
+                    if let FitnessFn::Weighted {
+                        lifespan,
+                        kills,
+                        accuracy,
+                    } = &mut config.fitness
+                    {
+                        ui.drag(hash!(), "Lifespan", Some((0., 10.)), lifespan);
+                        ui.drag(hash!(), "Kills", Some((0., 1000.)), kills);
+                        ui.drag(hash!(), "Accuracy", Some((0., 10000.)), accuracy);
+                    }
+                    if config.fitness != pop.config.fitness {
+                        pop.change_fitness(config.fitness);
+                    }
+                    ui.label(None, " ");
+                    ui.checkbox(hash!(), "Recurrent (restarts)", &mut config.recurrent);
+                    if config.recurrent != pop.config.recurrent {
+                        pop = Population::new(
+                            size as usize,
+                            auto_switch,
+                            config.clone(),
+                            (WIDTH, HEIGHT),
+                            rng.fork(),
+                        );
+                    }
+                    ui.label(None, "Layer Activations");
+                    if !editable(&config.hlayers) {
+                        ui.label(None, "Kept as loaded");
+                    } else {
+                        let mut layers = config
+                            .hidden_activs
+                            .iter()
+                            .chain([&config.out_activ])
+                            .map(|a| {
+                                a.map_or(0, |a| activs.iter().position(|&x| x == a).unwrap() + 1)
+                            })
+                            .collect::<Vec<_>>();
+                        for (i, layer) in layers.iter_mut().enumerate() {
+                            let label = match i {
+                                3 => "Output".to_string(),
+                                _ => format!("Layer {}", i + 1),
+                            };
+                            ui.combo_box(hash!("activ", i), &label, &layer_activ_names, layer);
+                        }
+                        let layers = layers
+                            .iter()
+                            .map(|&l| l.checked_sub(1).map(|l| activs[l]))
+                            .collect::<Vec<_>>();
+                        if layers[..3] != pop.config.hidden_activs[..]
+                            || layers[3] != pop.config.out_activ
+                        {
+                            config.hidden_activs = layers[..3].to_vec();
+                            config.out_activ = layers[3];
+                            pop.change_layer_activs(config.hidden_activs.clone(), config.out_activ);
+                        }
+                    }
+                    ui.label(None, " ");
+                    ui.label(None, "Mutation");
+                    let mut mutation = match config.mutation {
+                        Mutation::Reset => 0,
+                        Mutation::Gaussian { .. } => 1,
+                        Mutation::Scaled { .. } => 2,
+                    };
+                    ui.combo_box(hash!(), "«Select»", &mutations, &mut mutation);
+                    config.mutation = match (mutation, config.mutation) {
+                        (0, _) => Mutation::Reset,
+                        (1, m @ Mutation::Gaussian { .. }) => m,
+                        (1, _) => Mutation::Gaussian { sigma: 0.1 },
+                        (_, m @ Mutation::Scaled { .. }) => m,
+                        _ => Mutation::Scaled { sigma: 0.2 },
+                    };
+                    if let Mutation::Gaussian { sigma } | Mutation::Scaled { sigma } =
+                        &mut config.mutation
+                    {
+                        ui.drag(hash!(), "Sigma", Some((0.001, 1.)), sigma);
+                    }
+                    if config.mutation != pop.config.mutation {
+                        pop.change_mutation(config.mutation);
+                    }
+                    ui.checkbox(hash!(), "Self-Adaptive", &mut config.self_adaptive);
+                    if config.self_adaptive {
+                        let brains = pop.worlds.iter().map(|w| w.see_brain());
+                        let (rate, sigma) =
+                            brains.fold((0., 0.), |(r, s), b| (r + b.mut_rate(), s + b.sigma()));
+                        let n = pop.worlds.len() as f32;
+                        ui.label(
+                            None,
+                            &format!("Mean Rate: {:.3}  Sigma: {:.3}", rate / n, sigma / n),
+                        );
+                    }
+                    ui.label(None, " ");
+                    ui.label(None, "Crossover");
+                    let mut crossover = match config.crossover {
+                        Crossover::Uniform => 0,
+                        Crossover::SinglePoint => 1,
+                        Crossover::MultiPoint { .. } => 2,
+                        Crossover::Neuron => 3,
+                        Crossover::Blend { .. } => 4,
+                        Crossover::None => 5,
+                    };
+                    ui.combo_box(hash!(), "«Select»", &crossovers, &mut crossover);
+                    config.crossover = match (crossover, config.crossover) {
+                        (0, _) => Crossover::Uniform,
+                        (1, _) => Crossover::SinglePoint,
+                        (2, c @ Crossover::MultiPoint { .. }) => c,
+                        (2, _) => Crossover::MultiPoint { points: 3 },
+                        (3, _) => Crossover::Neuron,
+                        (4, c @ Crossover::Blend { .. }) => c,
+                        (4, _) => Crossover::Blend { alpha: 0.5 },
+                        _ => Crossover::None,
+                    };
+                    match &mut config.crossover {
+                        Crossover::MultiPoint { points } => {
+                            let mut n = *points as u32;
+                            ui.drag(hash!(), "Points", Some((1, 20)), &mut n);
+                            *points = n as usize;
+                        }
+                        Crossover::Blend { alpha } => {
+                            ui.drag(hash!(), "Alpha", Some((0., 1.)), alpha);
+                        }
+                        _ => {}
+                    }
+                    ui.label(None, " ");
+                    ui.label(None, "Parent Selection");
+                    let mut selection = match config.selection {
+                        Selection::Roulette => 0,
+                        Selection::Tournament { .. } => 1,
+                        Selection::Rank => 2,
+                        Selection::StochasticUniversal => 3,
+                        Selection::Truncation { .. } => 4,
+                    };
+                    ui.combo_box(hash!(), "«Select»", &selections, &mut selection);
+                    config.selection = match (selection, config.selection) {
+                        (0, _) => Selection::Roulette,
+                        (1, s @ Selection::Tournament { .. }) => s,
+                        (1, _) => Selection::Tournament { k: 3 },
+                        (2, _) => Selection::Rank,
+                        (3, _) => Selection::StochasticUniversal,
+                        (_, s @ Selection::Truncation { .. }) => s,
+                        _ => Selection::Truncation { frac: 0.2 },
+                    };
+                    match &mut config.selection {
+                        Selection::Tournament { k } => {
+                            let mut size = *k as u32;
+                            ui.drag(hash!(), "Tournament Size", Some((1, 20)), &mut size);
+                            *k = size as usize;
+                        }
+                        Selection::Truncation { frac } => {
+                            ui.drag(hash!(), "Top Fraction", Some((0.01, 1.)), frac);
+                        }
+                        _ => {}
+                    }
+                    ui.label(None, " ");
+                    ui.label(None, "Elitism");
+                    ui.drag(
+                        hash!(),
+                        "Fraction Kept",
+                        Some((0., 0.5)),
+                        &mut config.elitism,
+                    );
+                    ui.drag(
+                        hash!(),
+                        "Evaluations",
+                        Some((1, 10)),
+                        &mut config.elite_evals,
+                    );
+                    ui.label(None, " ");
+                    ui.label(None, "Asteroid Fields");
+                    ui.checkbox(hash!(), "Same for All Ships", &mut config.common_scenario);
+                    ui.drag(hash!(), "Episodes", Some((1, 10)), &mut config.episodes);
+                    if config.episodes > 1 {
+                        let mut aggregate = match config.aggregate {
+                            Aggregate::Mean => 0,
+                            Aggregate::Min => 1,
+                            Aggregate::Median => 2,
+                        };
+                        ui.combo_box(hash!(), "Combine", &aggregates, &mut aggregate);
+                        config.aggregate = match aggregate {
+                            0 => Aggregate::Mean,
+                            1 => Aggregate::Min,
+                            _ => Aggregate::Median,
+                        };
+                        ui.checkbox(hash!(), "In Parallel", &mut config.parallel_episodes);
+                    }
+                    ui.label(None, " ");
+                    ui.checkbox(
+                        hash!(),
+                        "Multi-Objective (NSGA-II)",
+                        &mut config.multi_objective,
+                    );
+                    if config.multi_objective {
+                        ui.label(None, "Ranks on lifespan, kills and accuracy");
+                    }
+                    let mut novelty = config.novelty.is_some();
+                    ui.checkbox(hash!(), "Novelty Search", &mut novelty);
+                    config.novelty = match (novelty, config.novelty) {
+                        (true, n @ Some(_)) => n,
+                        (true, None) => Some(Novelty::default()),
+                        (false, _) => None,
+                    };
+                    if let Some(novelty) = &mut config.novelty {
+                        ui.drag(hash!(), "Weight", Some((0., 1.)), &mut novelty.weight);
+                        let mut k = novelty.neighbours as u32;
+                        ui.drag(hash!(), "Neighbours", Some((1, 50)), &mut k);
+                        novelty.neighbours = k as usize;
+                        let mut archived = novelty.archived as u32;
+                        ui.drag(hash!(), "Archived per Gen", Some((0, 20)), &mut archived);
+                        novelty.archived = archived as usize;
+                        let mut capacity = novelty.capacity as u32;
+                        ui.drag(hash!(), "Archive Size", Some((0, 2000)), &mut capacity);
+                        novelty.capacity = capacity as usize;
+                        ui.label(None, &format!("Archive: {}", pop.archive.len()));
+                    }
+                    ui.label(None, " ");
+                    ui.label(None, "Stop and Save When");
+                    let stop = &mut config.stop;
+                    let mut on = stop.target.is_some();
+                    ui.checkbox(hash!(), "Fitness Reaches", &mut on);
+                    stop.target = on.then(|| stop.target.unwrap_or(1000.));
+                    if let Some(target) = &mut stop.target {
+                        ui.drag(hash!(), "Target", Some((0., 100000.)), target);
+                    }
+                    let mut on = stop.generations.is_some();
+                    ui.checkbox(hash!(), "Generation Reaches", &mut on);
+                    stop.generations = on.then(|| stop.generations.unwrap_or(100));
+                    if let Some(generations) = &mut stop.generations {
+                        let mut n = *generations as u32;
+                        ui.drag(hash!(), "Generations", Some((1, 10000)), &mut n);
+                        *generations = n as i32;
+                    }
+                    let mut on = stop.patience.is_some();
+                    ui.checkbox(hash!(), "No Improvement", &mut on);
+                    stop.patience = on.then(|| stop.patience.unwrap_or(30));
+                    if let Some(patience) = &mut stop.patience {
+                        let mut n = *patience as u32;
+                        ui.drag(hash!(), "For Generations", Some((1, 1000)), &mut n);
+                        *patience = n as i32;
+                    }
+                    let mut on = stop.seconds.is_some();
+                    ui.checkbox(hash!(), "Time Runs Out", &mut on);
+                    stop.seconds = on.then(|| stop.seconds.unwrap_or(600.));
+                    if let Some(seconds) = &mut stop.seconds {
+                        let mut minutes = *seconds / 60.;
+                        ui.drag(hash!(), "Minutes", Some((1., 600.)), &mut minutes);
+                        *seconds = minutes * 60.;
+                    }
+                    // New conditions are checked again from the next generation
+                    if config.stop != pop.config.stop {
+                        pop.config.stop = config.stop;
+                        pop.stopped = None;
+                    }
+                    // Only used when breeding, so they can change mid generation
+                    pop.config.self_adaptive = config.self_adaptive;
+                    pop.config.crossover = config.crossover;
+                    pop.config.brain = config.brain;
+                    pop.config.speciation = config.speciation;
+                    pop.config.species_threshold = config.species_threshold;
+                    pop.config.stagnation = config.stagnation;
+                    pop.config.selection = config.selection;
+                    pop.config.elitism = config.elitism;
+                    pop.config.elite_evals = config.elite_evals;
+                    pop.config.common_scenario = config.common_scenario;
+                    pop.config.episodes = config.episodes;
+                    pop.config.aggregate = config.aggregate;
+                    pop.config.parallel_episodes = config.parallel_episodes;
+                    pop.config.islands = config.islands;
+                    pop.config.multi_objective = config.multi_objective;
+                    pop.config.novelty = config.novelty;
+                    ui.pop_skin();
+                },
+            );
+        }
+        if hall {
+            root_ui().window(
+                hash!(),
+                vec2(2. * th, 2. * th),
+                vec2(settings_width, HEIGHT - 2. * th),
+                |ui| {
+                    ui.push_skin(&skin2);
+                    ui.label(None, "Hall of Fame");
+                    let mut kept = config.hall_of_fame as u32;
+                    ui.drag(hash!(), "Size", Some((1, 100)), &mut kept);
+                    config.hall_of_fame = kept as usize;
+                    let mut reinject = config.reinject as u32;
+                    ui.drag(hash!(), "Rejoin per Gen", Some((0, 20)), &mut reinject);
+                    config.reinject = reinject as usize;
+                    pop.config.hall_of_fame = config.hall_of_fame;
+                    pop.config.reinject = config.reinject;
+                    ui.label(None, " ");
+                    if pop.hall.entries.is_empty() {
+                        ui.label(None, "Waiting for generations...");
+                    }
+                    for (i, entry) in pop.hall.entries.iter().enumerate() {
+                        ui.label(
+                            None,
+                            &format!("#{} Gen {}, Fitness {:.1}", i + 1, entry.gen, entry.fitness),
+                        );
+                        ui.label(
+                            None,
+                            &format!(
+                                "   Lifespan {:.0}, Kills {:.0}, Accuracy {:.0}%",
+                                entry.lifespan,
+                                entry.kills,
+                                entry.accuracy * 100.
+                            ),
+                        );
+                        if ui.button(None, format!("Load #{}", i + 1)) {
+                            load = Some((
+                                entry.brain.clone(),
+                                Some(entry.id),
+                                pop.hall.clone(),
+                                pop.genealogy.clone(),
+                            ));
+                        }
+                    }
+                    ui.pop_skin();
+                },
+            );
+        }
+        if lineage {
+            root_ui().window(
+                hash!(),
+                vec2(2. * th, 2. * th),
+                vec2(settings_width, HEIGHT - 2. * th),
+                |ui| {
+                    ui.push_skin(&skin2);
+                    let id = lineage_focus.unwrap_or(pop.worlds[pop.track].id);
+                    if lineage_focus.is_some() && ui.button(None, "Back to Tracked Ship") {
+                        lineage_focus = None;
+                    }
+                    match pop.genealogy.get(id) {
+                        Some(record) => {
+                            ui.label(None, &format!("Ship #{}", record.id));
+                            ui.label(
+                                None,
+                                &format!(
+                                    "Made in generation {}, best fitness {:.1}",
+                                    record.gen, record.fitness
+                                ),
+                            );
+                            ui.label(
+                                None,
+                                &format!(
+                                    "Mutations: {} ({} along its line)",
+                                    record.mutations, record.line
+                                ),
+                            );
+                            ui.label(None, " ");
+                            if record.parents.is_empty() {
+                                ui.label(None, "First generation, no parents");
+                            } else {
+                                ui.label(None, "Parents (fitter first):");
+                            }
+                            for &parent in &record.parents {
+                                if ui.button(None, format!("Go to #{}", parent)) {
+                                    lineage_focus = Some(parent);
+                                }
+                            }
+                            ui.label(None, " ");
+                            ui.label(None, "Ancestry");
+                            for (depth, record) in pop.genealogy.tree(id, 4) {
+                                ui.label(
+                                    None,
+                                    &format!(
+                                        "{}#{} gen {}, fitness {:.1}, +{}",
+                                        "    ".repeat(depth),
+                                        record.id,
+                                        record.gen,
+                                        record.fitness,
+                                        record.mutations
+                                    ),
+                                );
+                            }
+                        }
+                        None => ui.label(None, "No record of this ship"),
+                    }
+                    ui.pop_skin();
+                },
+            );
+        }
+        if let Some((brain, id, hall_of_fame, genealogy)) = load {
+            size = 1;
+            config.adopt(&brain);
+            if editable(&config.hlayers) {
+                config.hidden_activs.resize(3, None);
+            }
+            activ = activs.iter().position(|&x| x == config.activ).unwrap();
+
+            prev_hlayers = config.hlayers.clone();
+            prev_mut_rate = config.mut_rate;
+            prev_activ = activ;
+
+            pop = Population::seeded(
+                vec![brain],
+                auto_switch,
+                config.clone(),
+                (WIDTH, HEIGHT),
+                rng.fork(),
+            );
+            // A brain from the hall of fame keeps its ancestry
+            if let Some(id) = id {
+                pop.worlds[0].id = id;
+                pop.genealogy = genealogy;
+            }
+            pop.hall = hall_of_fame;
+            human = false;
+        }
         next_frame().await;
     }
 }
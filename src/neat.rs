@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    nn::{self_adapt, ActivationFunc, Mutation},
+    rng::Rng,
+};
+
+// Weighted link between two nodes, lined up with the same link in other genomes by its
+// innovation number
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Conn {
+    pub innovation: usize,
+    pub from: usize,
+    pub to: usize,
+    pub weight: f32,
+    pub enabled: bool,
+}
+
+// Hands out innovation numbers and node ids to new structure. The same mutation made twice in
+// one generation gets the same numbers, so both genomes still line up in crossover.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Innovations {
+    next_innovation: usize,
+    next_node: usize,
+    #[serde(skip)]
+    conns: HashMap<(usize, usize), usize>,
+    #[serde(skip)]
+    splits: HashMap<usize, usize>,
+}
+
+impl Innovations {
+    // Numbers after the ones of the initial, fully connected genomes
+    pub fn new(inputs: usize, outputs: usize) -> Self {
+        Self {
+            next_innovation: (inputs + 1) * outputs,
+            next_node: inputs + 1 + outputs,
+            ..Default::default()
+        }
+    }
+
+    // Moves past the numbers a genome from elsewhere already uses, so new structure does not
+    // reuse them
+    pub fn cover(&mut self, genome: &Genome) {
+        if let Some(last) = genome.conns.iter().map(|c| c.innovation).max() {
+            self.next_innovation = self.next_innovation.max(last + 1);
+        }
+        if let Some(&last) = genome.hidden.iter().max() {
+            self.next_node = self.next_node.max(last + 1);
+        }
+    }
+
+    // Called once per generation
+    pub fn clear(&mut self) {
+        self.conns.clear();
+        self.splits.clear();
+    }
+
+    fn conn(&mut self, from: usize, to: usize) -> usize {
+        *self.conns.entry((from, to)).or_insert_with(|| {
+            self.next_innovation += 1;
+            self.next_innovation - 1
+        })
+    }
+
+    // Node placed on the connection with the given innovation number
+    fn split(&mut self, innovation: usize) -> usize {
+        *self.splits.entry(innovation).or_insert_with(|| {
+            self.next_node += 1;
+            self.next_node - 1
+        })
+    }
+}
+
+// NEAT genome: a network whose topology grows by mutation. Node ids are the inputs first,
+// then the bias, then the outputs, then hidden nodes in the order they were created.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Genome {
+    pub inputs: usize,
+    pub outputs: usize,
+    // Every connection goes from an earlier node to a later one in this order, inputs coming
+    // before and outputs after all of them, so the network never has cycles
+    pub hidden: Vec<usize>,
+    // Sorted by innovation number
+    pub conns: Vec<Conn>,
+    pub activ_func: ActivationFunc,
+    #[serde(default)]
+    pub out_activ: Option<ActivationFunc>,
+    pub mut_rate: f32,
+    pub sigma: f32,
+}
+
+impl Genome {
+    // Every input and the bias connected straight to every output
+    pub fn new(
+        inputs: usize,
+        outputs: usize,
+        mut_rate: f32,
+        activ: ActivationFunc,
+        rng: &mut Rng,
+    ) -> Self {
+        Self {
+            inputs,
+            outputs,
+            hidden: vec![],
+            conns: (0..=inputs)
+                .flat_map(|from| (0..outputs).map(move |to| (from, to)))
+                .map(|(from, to)| Conn {
+                    innovation: from * outputs + to,
+                    from,
+                    to: inputs + 1 + to,
+                    weight: rng.gen_normal() * (2. / outputs as f32).sqrt(),
+                    enabled: true,
+                })
+                .collect(),
+            activ_func: activ,
+            out_activ: None,
+            mut_rate,
+            sigma: 0.1,
+        }
+    }
+
+    fn is_output(&self, node: usize) -> bool {
+        (self.inputs + 1..self.inputs + 1 + self.outputs).contains(&node)
+    }
+
+    // Position of a node in evaluation order
+    fn rank(&self, node: usize) -> usize {
+        if node <= self.inputs {
+            0
+        } else if self.is_output(node) {
+            self.hidden.len() + 1
+        } else {
+            self.hidden.iter().position(|&n| n == node).unwrap() + 1
+        }
+    }
+
+    pub fn feed_forward(&self, inputs: &[f32]) -> Vec<f32> {
+        let io = self.inputs + 1 + self.outputs;
+        let mut values = vec![0.; self.hidden.iter().max().map_or(io, |&n| io.max(n + 1))];
+        values[..self.inputs].copy_from_slice(inputs);
+        values[self.inputs] = 1.;
+        let out_activ = self.out_activ.unwrap_or(self.activ_func);
+        for (node, activ) in self
+            .hidden
+            .iter()
+            .map(|&n| (n, self.activ_func))
+            .chain((self.inputs + 1..io).map(|n| (n, out_activ)))
+        {
+            let sum = self
+                .conns
+                .iter()
+                .filter(|c| c.enabled && c.to == node)
+                .map(|c| values[c.from] * c.weight)
+                .sum::<f32>();
+            values[node] = activ.apply(sum);
+        }
+        values[self.inputs + 1..io].to_vec()
+    }
+
+    // The first parent should be the fitter one, the child gets its structure
+    pub fn crossover(a: &Genome, b: &Genome, rng: &mut Rng) -> Self {
+        let mut child = a.clone();
+        for conn in &mut child.conns {
+            if let Ok(i) = b
+                .conns
+                .binary_search_by_key(&conn.innovation, |c| c.innovation)
+            {
+                let other = &b.conns[i];
+                if rng.gen_range(0., 1.) < 0.5 {
+                    conn.weight = other.weight;
+                }
+                // Disabled in either parent stays disabled most of the time
+                conn.enabled = (conn.enabled && other.enabled) || rng.gen_range(0., 1.) < 0.25;
+            }
+        }
+        child.mut_rate = (a.mut_rate + b.mut_rate) * 0.5;
+        child.sigma = (a.sigma + b.sigma) * 0.5;
+        child
+    }
+
+    // Returns the number of weights changed plus the connections and nodes added
+    pub fn mutate(
+        &mut self,
+        mutation: Mutation,
+        (add_conn, add_node): (f32, f32),
+        innovations: &mut Innovations,
+        rng: &mut Rng,
+    ) -> u32 {
+        let mut mutated = 0;
+        for conn in &mut self.conns {
+            if rng.gen_range(0., 1.) < self.mut_rate {
+                match mutation {
+                    Mutation::Reset => conn.weight = rng.gen_normal(),
+                    Mutation::Gaussian { .. } => conn.weight += self.sigma * rng.gen_normal(),
+                    Mutation::Scaled { .. } => conn.weight *= 1. + self.sigma * rng.gen_normal(),
+                }
+                mutated += 1;
+            }
+        }
+        if rng.gen_range(0., 1.) < add_conn {
+            // Any input, the bias or a hidden node, to any hidden node or output
+            let from = rng.gen_range(0, self.inputs + 1 + self.hidden.len());
+            let from = if from <= self.inputs {
+                from
+            } else {
+                self.hidden[from - self.inputs - 1]
+            };
+            let to = rng.gen_range(0, self.hidden.len() + self.outputs);
+            let to = match self.hidden.get(to) {
+                Some(&n) => n,
+                None => self.inputs + 1 + to - self.hidden.len(),
+            };
+            if self.rank(from) < self.rank(to)
+                && !self.conns.iter().any(|c| c.from == from && c.to == to)
+            {
+                self.insert(Conn {
+                    innovation: innovations.conn(from, to),
+                    from,
+                    to,
+                    weight: rng.gen_normal(),
+                    enabled: true,
+                });
+                mutated += 1;
+            }
+        }
+        let enabled = self.conns.iter().filter(|c| c.enabled).count();
+        if enabled > 0 && rng.gen_range(0., 1.) < add_node {
+            let i = self
+                .conns
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| c.enabled)
+                .nth(rng.gen_range(0, enabled))
+                .unwrap()
+                .0;
+            let Conn {
+                innovation,
+                from,
+                to,
+                weight,
+                ..
+            } = self.conns[i];
+            let node = innovations.split(innovation);
+            if !self.hidden.contains(&node) {
+                self.conns[i].enabled = false;
+                // Right before the old target keeps every connection pointing forward
+                match self.hidden.iter().position(|&n| n == to) {
+                    Some(pos) => self.hidden.insert(pos, node),
+                    None => self.hidden.push(node),
+                }
+                self.insert(Conn {
+                    innovation: innovations.conn(from, node),
+                    from,
+                    to: node,
+                    weight: 1.,
+                    enabled: true,
+                });
+                self.insert(Conn {
+                    innovation: innovations.conn(node, to),
+                    from: node,
+                    to,
+                    weight,
+                    enabled: true,
+                });
+                mutated += 1;
+            }
+        }
+        mutated
+    }
+
+    fn insert(&mut self, conn: Conn) {
+        let i = self
+            .conns
+            .partition_point(|c| c.innovation < conn.innovation);
+        self.conns.insert(i, conn);
+    }
+
+    pub fn adapt(&mut self, rng: &mut Rng) {
+        self_adapt(&mut self.mut_rate, &mut self.sigma, self.conns.len(), rng);
+    }
+
+    // Compatibility distance from the NEAT paper: genes only one genome has, plus the mean
+    // weight difference of the genes both have
+    pub fn distance(a: &Genome, b: &Genome) -> f32 {
+        let (mut i, mut j) = (0, 0);
+        let (mut disjoint, mut matching, mut diff) = (0, 0, 0.);
+        while i < a.conns.len() && j < b.conns.len() {
+            let (x, y) = (&a.conns[i], &b.conns[j]);
+            if x.innovation == y.innovation {
+                matching += 1;
+                diff += (x.weight - y.weight).abs();
+                i += 1;
+                j += 1;
+            } else if x.innovation < y.innovation {
+                disjoint += 1;
+                i += 1;
+            } else {
+                disjoint += 1;
+                j += 1;
+            }
+        }
+        let excess = a.conns.len() - i + b.conns.len() - j;
+        // Small genomes are not normalized
+        let n = match a.conns.len().max(b.conns.len()) {
+            n if n < 20 => 1.,
+            n => n as f32,
+        };
+        (excess + disjoint) as f32 / n + 0.4 * diff / matching.max(1) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grown(rng: &mut Rng, innovations: &mut Innovations, steps: usize) -> Genome {
+        let mut genome = Genome::new(4, 2, 0.1, ActivationFunc::ReLU, rng);
+        for _ in 0..steps {
+            innovations.clear();
+            genome.mutate(Mutation::Reset, (0.8, 0.5), innovations, rng);
+        }
+        genome
+    }
+
+    #[test]
+    fn distance() {
+        let mut rng = Rng::new(5, 0);
+        let mut innovations = Innovations::new(4, 2);
+        let a = grown(&mut rng, &mut innovations, 10);
+        assert_eq!(Genome::distance(&a, &a), 0.);
+        let mut b = a.clone();
+        b.mutate(Mutation::Reset, (1., 0.), &mut innovations, &mut rng);
+        let mut c = b.clone();
+        for _ in 0..10 {
+            c.mutate(Mutation::Reset, (1., 1.), &mut innovations, &mut rng);
+        }
+        assert_eq!(Genome::distance(&a, &b), Genome::distance(&b, &a));
+        assert!(Genome::distance(&a, &c) > Genome::distance(&a, &b));
+    }
+
+    #[test]
+    fn crossover_keeps_the_fitter_structure() {
+        let mut rng = Rng::new(6, 0);
+        let mut innovations = Innovations::new(4, 2);
+        let a = grown(&mut rng, &mut innovations, 20);
+        let b = grown(&mut rng, &mut innovations, 20);
+        let child = Genome::crossover(&a, &b, &mut rng);
+        assert_eq!(child.hidden, a.hidden);
+        let innovations = |g: &Genome| g.conns.iter().map(|c| c.innovation).collect::<Vec<_>>();
+        assert_eq!(innovations(&child), innovations(&a));
+        assert!(innovations(&child).windows(2).all(|w| w[0] < w[1]));
+        // Matching genes take either parent's weight
+        for conn in &child.conns {
+            let from_a = a
+                .conns
+                .iter()
+                .find(|c| c.innovation == conn.innovation)
+                .unwrap();
+            let from_b = b.conns.iter().find(|c| c.innovation == conn.innovation);
+            assert!(
+                conn.weight == from_a.weight || from_b.is_some_and(|c| c.weight == conn.weight)
+            );
+        }
+        assert_eq!(child.feed_forward(&[0.1, 0.2, 0.3, 0.4]).len(), 2);
+    }
+
+    #[test]
+    fn cover_moves_past_existing_numbers() {
+        let mut rng = Rng::new(8, 0);
+        let genome = grown(&mut rng, &mut Innovations::new(4, 2), 30);
+        let mut innovations = Innovations::new(4, 2);
+        innovations.cover(&genome);
+        let mut copy = genome.clone();
+        for _ in 0..10 {
+            innovations.clear();
+            copy.mutate(Mutation::Reset, (1., 1.), &mut innovations, &mut rng);
+        }
+        let mut numbers = copy.conns.iter().map(|c| c.innovation).collect::<Vec<_>>();
+        numbers.dedup();
+        assert_eq!(numbers.len(), copy.conns.len());
+    }
+}
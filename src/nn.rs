@@ -1,15 +1,103 @@
-use std::f32::consts::PI;
-
-use macroquad::{prelude::*, rand::gen_range};
 use nalgebra::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+use crate::rng::Rng;
+
+#[derive(PartialEq, Debug, Clone, Copy, Default, Serialize, Deserialize)]
 
 pub enum ActivationFunc {
+    #[default]
     ReLU,
     Sigmoid,
     Tanh,
+    // ReLU with a slope of 0.01 below zero
+    LeakyReLU,
+    ELU,
+    Softsign,
+    // Bell curve, largest at zero
+    Gaussian,
+    Identity,
+    // 1 above zero, 0 otherwise
+    Step,
+}
+
+impl ActivationFunc {
+    pub fn apply(self, x: f32) -> f32 {
+        match self {
+            ActivationFunc::ReLU => x.max(0.),
+            ActivationFunc::Sigmoid => 1. / (1. + (-x).exp()),
+            ActivationFunc::Tanh => x.tanh(),
+            ActivationFunc::LeakyReLU => x.max(0.01 * x),
+            ActivationFunc::ELU => {
+                if x > 0. {
+                    x
+                } else {
+                    x.exp() - 1.
+                }
+            }
+            ActivationFunc::Softsign => x / (1. + x.abs()),
+            ActivationFunc::Gaussian => (-x * x).exp(),
+            ActivationFunc::Identity => x,
+            ActivationFunc::Step => {
+                if x > 0. {
+                    1.
+                } else {
+                    0.
+                }
+            }
+        }
+    }
+}
+
+// How a weight picked for mutation is changed. The sigma is handed to every network,
+// which may then tune its own copy when mutation is self-adaptive.
+#[derive(PartialEq, Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum Mutation {
+    // Replaced by a new standard normal sample
+    #[default]
+    Reset,
+    // Nudged by a normal sample with the given standard deviation
+    Gaussian {
+        sigma: f32,
+    },
+    // Nudged in proportion to its own size, so small weights are refined finely
+    Scaled {
+        sigma: f32,
+    },
+}
+
+// How the weights of two parents are combined into a child
+#[derive(PartialEq, Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum Crossover {
+    // Every weight from either parent at random
+    #[default]
+    Uniform,
+    // Weights before a random position in the flattened genome from one parent, the rest
+    // from the other
+    SinglePoint,
+    // Parents alternate at several random positions
+    MultiPoint {
+        points: usize,
+    },
+    // Every neuron keeps all its incoming weights from one parent
+    Neuron,
+    // Every weight from a random point on the line through both parents' weights, reaching
+    // past them by alpha times their distance
+    Blend {
+        alpha: f32,
+    },
+    // The child is a copy of the first parent, mutation alone makes it differ
+    None,
+}
+
+pub(crate) fn self_adapt(mut_rate: &mut f32, sigma: &mut f32, genes: usize, rng: &mut Rng) {
+    let tau = 1. / (genes as f32).sqrt();
+    *mut_rate = (*mut_rate * (tau * rng.gen_normal()).exp()).clamp(0.001, 1.);
+    *sigma = (*sigma * (tau * rng.gen_normal()).exp()).clamp(0.001, 10.);
+}
+
+fn default_sigma() -> f32 {
+    0.1
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -17,12 +105,22 @@ pub struct NN {
     pub config: Vec<usize>,
     pub weights: Vec<DMatrix<f32>>,
     pub activ_func: ActivationFunc,
+    // Activation of each layer after the input one, `activ_func` where missing or None
+    #[serde(default)]
+    pub layer_activs: Vec<Option<ActivationFunc>>,
     pub mut_rate: f32,
+    // Spread of Gaussian and scaled mutations
+    #[serde(default = "default_sigma")]
+    pub sigma: f32,
+    // Weights from each hidden layer's previous output back into it, empty for
+    // feed-forward networks
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recurrent: Vec<DMatrix<f32>>,
 }
 
 impl NN {
     // Vec of number of neurons in input, hidden 1, hidden 2, ..., output layers
-    pub fn new(config: Vec<usize>, mut_rate: f32, activ: ActivationFunc) -> Self {
+    pub fn new(config: Vec<usize>, mut_rate: f32, activ: ActivationFunc, rng: &mut Rng) -> Self {
         Self {
             config: config
                 .iter()
@@ -35,161 +133,177 @@ impl NN {
                 .iter()
                 .zip(config.iter().skip(1))
                 .map(|(&curr, &last)| {
-                    // DMatrix::from_fn(last, curr + 1, |_, _| gen_range(-1., 1.))
-                    DMatrix::from_fn(last, curr + 1, |_, _| {
-                        // uniform random to standard normal
-                        (-2. * gen_range(0., 1.).ln()).sqrt() * (PI * gen_range(0., 2.)).cos()
-                    }) * (2. / last as f32).sqrt()
+                    // DMatrix::from_fn(last, curr + 1, |_, _| rng.gen_range(-1., 1.))
+                    DMatrix::from_fn(last, curr + 1, |_, _| rng.gen_normal())
+                        * (2. / last as f32).sqrt()
                 })
                 .collect(),
 
             mut_rate,
+            sigma: default_sigma(),
             activ_func: activ,
+            layer_activs: vec![],
+            recurrent: vec![],
         }
     }
 
-    pub fn crossover(a: &NN, b: &NN) -> Self {
+    pub fn crossover(a: &NN, b: &NN, crossover: Crossover, rng: &mut Rng) -> Self {
         assert_eq!(a.config, b.config, "NN configs not same.");
+        assert_eq!(
+            a.recurrent.len(),
+            b.recurrent.len(),
+            "NN recurrence not same."
+        );
+        if crossover == Crossover::None {
+            return a.clone();
+        }
+        // Positions in the flattened genome where the child switches parent
+        let len = a.genome().map(|w| w.len()).sum::<usize>();
+        let mut cuts = match crossover {
+            Crossover::SinglePoint => vec![rng.gen_range(0, len)],
+            Crossover::MultiPoint { points } => {
+                (0..points).map(|_| rng.gen_range(0, len)).collect()
+            }
+            _ => vec![],
+        };
+        cuts.sort();
+        let mut pos = 0;
+        let mut weights = a
+            .genome()
+            .zip(b.genome())
+            .map(|(m1, m2)| match crossover {
+                Crossover::Uniform => m1.zip_map(m2, |ele1, ele2| {
+                    if rng.gen_range(0., 1.) < 0.5 {
+                        ele1
+                    } else {
+                        ele2
+                    }
+                }),
+                Crossover::SinglePoint | Crossover::MultiPoint { .. } => {
+                    m1.zip_map(m2, |ele1, ele2| {
+                        let switches = cuts.iter().take_while(|&&c| c <= pos).count();
+                        pos += 1;
+                        if switches % 2 == 0 {
+                            ele1
+                        } else {
+                            ele2
+                        }
+                    })
+                }
+                // Rows hold the incoming weights of a neuron
+                Crossover::Neuron => {
+                    let mut m = m1.clone();
+                    for row in 0..m.nrows() {
+                        if rng.gen_range(0., 1.) < 0.5 {
+                            m.set_row(row, &m2.row(row));
+                        }
+                    }
+                    m
+                }
+                Crossover::Blend { alpha } => m1.zip_map(m2, |ele1, ele2| {
+                    ele1 + rng.gen_range(-alpha, 1. + alpha) * (ele2 - ele1)
+                }),
+                Crossover::None => unreachable!(),
+            })
+            .collect::<Vec<_>>();
         Self {
             config: a.config.to_owned(),
             activ_func: a.activ_func,
-            mut_rate: a.mut_rate,
-            weights: a
-                .weights
-                .iter()
-                .zip(b.weights.iter())
-                .map(|(m1, m2)| {
-                    m1.zip_map(
-                        m2,
-                        |ele1, ele2| if gen_range(0., 1.) < 0.5 { ele1 } else { ele2 },
-                    )
-                })
-                .collect(),
+            layer_activs: a.layer_activs.clone(),
+            // Equal unless mutation is self-adaptive
+            mut_rate: (a.mut_rate + b.mut_rate) * 0.5,
+            sigma: (a.sigma + b.sigma) * 0.5,
+            recurrent: weights.split_off(a.weights.len()),
+            weights,
         }
     }
 
-    pub fn mutate(&mut self) {
-        for weight in &mut self.weights {
+    // Returns the number of weights changed
+    pub fn mutate(&mut self, mutation: Mutation, rng: &mut Rng) -> u32 {
+        let mut mutated = 0;
+        for weight in self.weights.iter_mut().chain(&mut self.recurrent) {
             for ele in weight {
-                if gen_range(0., 1.) < self.mut_rate {
-                    // *ele += gen_range(-1., 1.);
-                    // uniform random to standard normal
-                    *ele = (-2. * gen_range(0., 1.).ln()).sqrt() * (PI * gen_range(0., 2.)).cos();
+                if rng.gen_range(0., 1.) < self.mut_rate {
+                    match mutation {
+                        Mutation::Reset => *ele = rng.gen_normal(),
+                        Mutation::Gaussian { .. } => *ele += self.sigma * rng.gen_normal(),
+                        Mutation::Scaled { .. } => *ele *= 1. + self.sigma * rng.gen_normal(),
+                    }
+                    mutated += 1;
                 }
             }
         }
+        mutated
     }
 
-    pub fn feed_forward(&self, inputs: &Vec<f32>) -> Vec<f32> {
-        // println!("inputs: {:?}", inputs);
-        let mut y = DMatrix::from_vec(inputs.len(), 1, inputs.to_vec());
-        for i in 0..self.config.len() - 1 {
-            y = (&self.weights[i] * y.insert_row(self.config[i] - 1, 1.)).map(|x| {
-                match self.activ_func {
-                    ActivationFunc::ReLU => x.max(0.),
-                    ActivationFunc::Sigmoid => 1. / (1. + (-x).exp()),
-                    ActivationFunc::Tanh => x.tanh(),
-                }
-            });
-        }
-        y.column(0).data.into_slice().to_vec()
+    // Log-normal step of the network's own mutation rate and sigma, done before `mutate` so
+    // that settings which produce better children are passed on with them
+    pub fn adapt(&mut self, rng: &mut Rng) {
+        let genes = self.weights.iter().map(|w| w.len()).sum();
+        self_adapt(&mut self.mut_rate, &mut self.sigma, genes, rng);
+    }
+
+    // Activation of the layer fed by `weights[layer]`
+    pub fn activ(&self, layer: usize) -> ActivationFunc {
+        self.activ_override(layer).unwrap_or(self.activ_func)
     }
 
-    pub fn draw(&self, width: f32, height: f32, inputs: &Vec<f32>, outputs: &Vec<f32>, bias: bool) {
-        draw_rectangle_lines(-width * 0.5, -height * 0.5, width, height, 2., WHITE);
+    pub fn activ_override(&self, layer: usize) -> Option<ActivationFunc> {
+        self.layer_activs.get(layer).copied().flatten()
+    }
 
-        let width = width * 0.8;
-        let height = height * 0.8;
-        let vspace = height / (self.config.iter().max().unwrap() - 1) as f32;
-        let mut p1s: Vec<(f32, f32)>;
-        let mut p2s: Vec<(f32, f32)> = Vec::new();
-        for (i, layer) in self
-            .config
+    // Gives hidden layers of a recurrent network a copy of their own previous output as
+    // extra input (Elman network)
+    pub fn add_recurrence(&mut self, rng: &mut Rng) {
+        self.recurrent = self.weights[..self.weights.len() - 1]
             .iter()
-            .take(self.config.len() - 1)
-            .map(|x| x - if bias { 0 } else { 1 })
-            .chain(self.config.last().copied())
-            .enumerate()
-        {
-            p1s = p2s;
-            p2s = Vec::new();
-            for neuron in 0..layer {
-                p2s.push((
-                    i as f32 * width / (self.config.len() - 1) as f32 - width * 0.5,
-                    neuron as f32 * vspace - (vspace * (layer - 1) as f32) * 0.5,
-                ));
-            }
-            for (k, j, p1, p2) in p1s.iter().enumerate().flat_map(|(k, x)| {
-                p2s.iter()
-                    .take(
-                        p2s.len()
-                            - if i == self.config.len() - 1 || !bias {
-                                0
-                            } else {
-                                1
-                            },
-                    )
-                    .enumerate()
-                    .map(move |(j, y)| (k, j, *x, *y))
-            }) {
-                let weight = *self.weights[i - 1].index((j, k));
-                let c = if weight < 0. { 0. } else { 1. };
-                draw_line(
-                    p1.0,
-                    p1.1,
-                    p2.0,
-                    p2.1,
-                    1.5,
-                    Color::new(1., c, c, weight.abs()),
-                );
-            }
+            .map(|w| {
+                DMatrix::from_fn(w.nrows(), w.nrows(), |_, _| rng.gen_normal())
+                    * (1. / w.nrows() as f32).sqrt()
+            })
+            .collect();
+    }
 
-            let mut inputs = inputs.to_vec();
-            inputs.push(1.);
-
-            for (j, p) in p1s.iter().enumerate() {
-                draw_circle(p.0, p.1, 10., WHITE);
-                draw_circle(p.0, p.1, 8., BLACK);
-                draw_circle(
-                    p.0,
-                    p.1,
-                    8.,
-                    if i == 1 && inputs.len() > 1 {
-                        let c = if inputs[j] < 0. { 0. } else { 1. };
-                        Color::new(1., c, c, inputs[j].abs())
-                    } else {
-                        BLACK
-                    },
-                );
-                if i == 1 && inputs.len() > 1 {
-                    draw_text(
-                        &format!("{:.2}", inputs[j]),
-                        p.0 - if inputs[j] < 0. { 50. } else { 42. },
-                        p.1 + 4.,
-                        16.,
-                        WHITE,
-                    );
-                }
+    // Mean absolute difference between the weights of two networks of the same shape
+    pub fn distance(a: &NN, b: &NN) -> f32 {
+        let (sum, len) = a
+            .genome()
+            .zip(b.genome())
+            .fold((0., 0), |(sum, len), (m1, m2)| {
+                (sum + (m1 - m2).abs().sum(), len + m1.len())
+            });
+        sum / len.max(1) as f32
+    }
+
+    // Every evolved matrix, recurrent ones last
+    fn genome(&self) -> impl Iterator<Item = &DMatrix<f32>> {
+        self.weights.iter().chain(&self.recurrent)
+    }
+
+    pub fn feed_forward(&self, inputs: &[f32]) -> Vec<f32> {
+        self.step(inputs, &mut vec![])
+    }
+
+    // Same as `feed_forward`, but a recurrent network keeps its hidden state in `state`
+    // between calls. Starts from zeros when `state` is empty.
+    pub fn step(&self, inputs: &[f32], state: &mut Vec<DMatrix<f32>>) -> Vec<f32> {
+        // println!("inputs: {:?}", inputs);
+        let mut y = DMatrix::from_vec(inputs.len(), 1, inputs.to_vec());
+        for i in 0..self.config.len() - 1 {
+            let mut z = &self.weights[i] * y.insert_row(self.config[i] - 1, 1.);
+            if let (Some(u), Some(h)) = (self.recurrent.get(i), state.get(i)) {
+                z += u * h;
             }
-        }
-        for (j, p) in p2s.iter().enumerate() {
-            draw_circle(p.0, p.1, 10., WHITE);
-            draw_circle(p.0, p.1, 8., BLACK);
-            if !outputs.is_empty() {
-                draw_circle(p.0, p.1, 8., Color::new(1., 1., 1., outputs[j]));
-                draw_text(
-                    &format!("{:.2}", outputs[j]),
-                    p.0 + 14.,
-                    p.1 + 4.,
-                    16.,
-                    WHITE,
-                );
+            let activ = self.activ(i);
+            y = z.map(|x| activ.apply(x));
+            if i < self.recurrent.len() {
+                match state.get_mut(i) {
+                    Some(h) => *h = y.clone(),
+                    None => state.push(y.clone()),
+                }
             }
         }
-        draw_rectangle(width * 0.47, height * 0.47, 10., 10., RED);
-        draw_text("-ve", width * 0.47 + 20., height * 0.47 + 10., 20.0, WHITE);
-        draw_rectangle(width * 0.47, height * 0.47 + 20., 10., 10., WHITE);
-        draw_text("+ve", width * 0.47 + 20., height * 0.47 + 30., 20.0, WHITE);
+        y.column(0).data.into_slice().to_vec()
     }
 
     pub fn export(&self) -> String {
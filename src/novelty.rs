@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+
+// Rewards ships for behaving unlike the rest of the population and the archive of past
+// behaviors, so the search keeps exploring instead of climbing the nearest fitness peak
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Novelty {
+    // 1 selects on novelty alone, 0 on fitness alone
+    pub weight: f32,
+    // Nearest behaviors averaged into the novelty of a ship
+    pub neighbours: usize,
+    // Most novel behaviors of each generation kept in the archive
+    pub archived: usize,
+    // Largest size of the archive, the oldest behaviors are dropped past it
+    pub capacity: usize,
+}
+
+impl Default for Novelty {
+    fn default() -> Self {
+        Self {
+            weight: 0.5,
+            neighbours: 15,
+            archived: 2,
+            capacity: 500,
+        }
+    }
+}
+
+impl Novelty {
+    // Selection scores of ships with the given fitness and behaviors, both scaled to [0, 1]
+    // before blending. The most novel behaviors are added to `archive`, which is then cut
+    // down to its capacity.
+    pub fn score(
+        &self,
+        fitness: &[f32],
+        behaviors: &[Vec<f32>],
+        archive: &mut Vec<Vec<f32>>,
+    ) -> Vec<f32> {
+        let novelty = behaviors
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                let mut dists = behaviors
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .map(|(_, o)| o)
+                    .chain(archive.iter())
+                    .map(|o| distance(b, o))
+                    .collect::<Vec<_>>();
+                dists.sort_by(|a, b| a.total_cmp(b));
+                let k = self.neighbours.clamp(1, dists.len().max(1));
+                dists.iter().take(k).sum::<f32>() / k as f32
+            })
+            .collect::<Vec<_>>();
+        let mut order = (0..behaviors.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| novelty[b].total_cmp(&novelty[a]));
+        archive.extend(
+            order
+                .iter()
+                .take(self.archived)
+                .map(|&i| behaviors[i].clone()),
+        );
+        let excess = archive.len().saturating_sub(self.capacity);
+        archive.drain(..excess);
+
+        let max_fitness = fitness.iter().copied().fold(0., f32::max);
+        let max_novelty = novelty.iter().copied().fold(0., f32::max);
+        fitness
+            .iter()
+            .zip(&novelty)
+            .map(|(&f, &n)| {
+                (1. - self.weight) * scale(f, max_fitness) + self.weight * scale(n, max_novelty)
+            })
+            .collect()
+    }
+}
+
+fn scale(x: f32, max: f32) -> f32 {
+    if max > 0. {
+        x / max
+    } else {
+        0.
+    }
+}
+
+fn distance(a: &[f32], b: &[f32]) -> f32 {
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y).powi(2))
+        .sum::<f32>()
+        .sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archive_keeps_the_newest_up_to_capacity() {
+        let novelty = Novelty {
+            archived: 3,
+            capacity: 10,
+            ..Default::default()
+        };
+        let mut archive = vec![];
+        for gen in 0..8 {
+            let behaviors = (0..6)
+                .map(|i| vec![gen as f32, i as f32])
+                .collect::<Vec<_>>();
+            novelty.score(&[1.; 6], &behaviors, &mut archive);
+            assert!(archive.len() <= 10);
+        }
+        assert_eq!(archive.len(), 10);
+        assert!(archive.iter().all(|b| b[0] >= 4.));
+    }
+
+    #[test]
+    fn blends_scaled_fitness_and_novelty() {
+        let novelty = Novelty {
+            weight: 0.5,
+            neighbours: 1,
+            archived: 0,
+            ..Default::default()
+        };
+        let behaviors = vec![vec![0.], vec![1.], vec![10.]];
+        let scores = novelty.score(&[4., 2., 0.], &behaviors, &mut vec![]);
+        // Fitness 1, 0.5, 0 and novelty 1/9, 1/9, 1
+        assert!((scores[0] - (0.5 + 0.5 / 9.)).abs() < 1e-6);
+        assert!((scores[1] - (0.25 + 0.5 / 9.)).abs() < 1e-6);
+        assert!((scores[2] - 0.5).abs() < 1e-6);
+    }
+}
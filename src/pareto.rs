@@ -0,0 +1,124 @@
+// NSGA-II: ships are ranked by the front of non-dominated ships they fall in, then by how
+// far they are from their neighbours on it (crowding distance), so the front spreads out
+// instead of piling up on one trade-off
+
+// Lifespan in frames, asteroids destroyed and accuracy, all to be maximized
+pub type Objectives = [f32; 3];
+
+// At least as good in every objective and better in one
+pub fn dominates(a: &Objectives, b: &Objectives) -> bool {
+    a.iter().zip(b).all(|(x, y)| x >= y) && a.iter().zip(b).any(|(x, y)| x > y)
+}
+
+// Indices of the points in each front, best front first
+pub fn fronts(points: &[Objectives]) -> Vec<Vec<usize>> {
+    let n = points.len();
+    let mut dominated = vec![vec![]; n];
+    let mut count = vec![0; n];
+    for i in 0..n {
+        for j in 0..n {
+            if dominates(&points[i], &points[j]) {
+                dominated[i].push(j);
+            } else if dominates(&points[j], &points[i]) {
+                count[i] += 1;
+            }
+        }
+    }
+    let mut fronts = vec![];
+    let mut front = (0..n).filter(|&i| count[i] == 0).collect::<Vec<_>>();
+    while !front.is_empty() {
+        let mut next = vec![];
+        for &i in &front {
+            for &j in &dominated[i] {
+                count[j] -= 1;
+                if count[j] == 0 {
+                    next.push(j);
+                }
+            }
+        }
+        next.sort();
+        fronts.push(front);
+        front = next;
+    }
+    fronts
+}
+
+// Crowding distance of every point of a front, infinite at the ends of each objective
+pub fn crowding(front: &[usize], points: &[Objectives]) -> Vec<f32> {
+    let mut dist = vec![0.; front.len()];
+    let objective = |m: usize| front.iter().map(|&i| points[i][m]).collect::<Vec<_>>();
+    for values in (0..3).map(objective) {
+        let mut order = (0..front.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+        let (first, last) = (order[0], order[order.len() - 1]);
+        let range = values[last] - values[first];
+        dist[first] = f32::INFINITY;
+        dist[last] = f32::INFINITY;
+        if range <= 0. {
+            continue;
+        }
+        for w in order.windows(3) {
+            dist[w[1]] += (values[w[2]] - values[w[0]]) / range;
+        }
+    }
+    dist
+}
+
+// Front of every point, and a score that orders points like the crowded comparison of
+// NSGA-II: better fronts first, less crowded first within a front. Scores are never
+// negative, so any selection method can use them as fitness.
+pub fn rank(points: &[Objectives]) -> (Vec<usize>, Vec<f32>) {
+    let fronts = fronts(points);
+    let mut ranks = vec![0; points.len()];
+    let mut scores = vec![0.; points.len()];
+    for (r, front) in fronts.iter().enumerate() {
+        for (&i, d) in front.iter().zip(crowding(front, points)) {
+            ranks[i] = r;
+            // Crowding squashed into [0, 0.5] so it never lifts a point into a better front
+            let spread = if d.is_finite() { d / (1. + d) } else { 1. };
+            scores[i] = (fronts.len() - r) as f32 + 0.5 * spread;
+        }
+    }
+    (ranks, scores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dominance() {
+        assert!(dominates(&[2., 1., 1.], &[1., 1., 1.]));
+        assert!(!dominates(&[1., 1., 1.], &[1., 1., 1.]));
+        assert!(!dominates(&[2., 0., 1.], &[1., 1., 1.]));
+    }
+
+    #[test]
+    fn fronts_and_scores() {
+        let points = [
+            [1., 1., 1.],
+            [3., 1., 1.],
+            [1., 3., 1.5],
+            [2., 2., 1.2],
+            [0., 0., 0.],
+            [1., 1., 0.5],
+        ];
+        assert_eq!(
+            fronts(&points),
+            vec![vec![1, 2, 3], vec![0], vec![5], vec![4]]
+        );
+        let (ranks, scores) = rank(&points);
+        assert_eq!(ranks, vec![1, 0, 0, 0, 3, 2]);
+        // Every point of a better front scores above every point of a worse one
+        for i in 0..points.len() {
+            for j in 0..points.len() {
+                if ranks[i] < ranks[j] {
+                    assert!(scores[i] > scores[j]);
+                }
+            }
+            assert!(scores[i] >= 0.);
+        }
+        // Ends of the front are less crowded than its middle
+        assert!(scores[1] > scores[3] && scores[2] > scores[3]);
+    }
+}
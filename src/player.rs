@@ -1,51 +1,52 @@
 use std::{f32::consts::PI, f64::consts::TAU};
 
-use macroquad::{prelude::*, rand::gen_range};
+use glam::{vec2, Vec2};
+use nalgebra::DMatrix;
 
 use crate::{
     asteroids::Asteroid,
+    brain::Brain,
+    neat::Genome,
     nn::{ActivationFunc, NN},
+    rng::Rng,
 };
+// Number of values a brain sees and of keys it presses
+pub const INPUTS: usize = 5;
+pub const OUTPUTS: usize = 4;
+
 #[derive(Default)]
 pub struct Player {
     pub pos: Vec2,
     vel: Vec2,
-    acc: f32,
+    pub(crate) acc: f32,
     pub dir: Vec2,
     rot: f32,
     drag: f32,
-    bullets: Vec<Bullet>,
-    asteroid: Option<Asteroid>,
-    inputs: Vec<f32>,
+    pub(crate) bullets: Vec<Bullet>,
+    pub(crate) asteroid: Option<Asteroid>,
+    pub(crate) inputs: Vec<f32>,
     pub outputs: Vec<f32>,
     // asteroid_data: Vec<(f32, f32, f32)>,
+    #[allow(dead_code)]
     raycasts: Vec<f32>,
     last_shot: u32,
     shot_interval: u32,
-    pub brain: Option<NN>,
+    pub brain: Option<Brain>,
+    // Hidden state of a recurrent brain, every ship starts with a blank one
+    memory: Vec<DMatrix<f32>>,
     alive: bool,
     pub lifespan: u32,
     pub shots: u32,
+    // Frames each key was pressed by the brain
+    pub(crate) presses: [u32; 4],
+    // Keys held by a human player (right, left, throttle, shoot), set by the front-end
+    pub input: [bool; 4],
 }
 
 impl Player {
-    pub fn new(
-        config: Option<Vec<usize>>,
-        mut_rate: Option<f32>,
-        activ: Option<ActivationFunc>,
-    ) -> Self {
+    pub fn new(brain: Option<Brain>) -> Self {
         Self {
-            brain: match config {
-                Some(mut c) => {
-                    c.retain(|&x| x != 0);
-                    // Number of inputs
-                    c.insert(0, 5);
-                    // Number of outputs
-                    c.push(4);
-                    Some(NN::new(c, mut_rate.unwrap(), activ.unwrap()))
-                }
-                _ => None,
-            },
+            brain,
             dir: vec2(0., -1.),
             rot: 1.5 * PI,
 
@@ -61,6 +62,24 @@ impl Player {
         }
     }
 
+    // Random brain sized for the player's inputs and outputs, with the given hidden layers
+    pub fn new_brain(
+        mut hlayers: Vec<usize>,
+        mut_rate: f32,
+        activ: ActivationFunc,
+        rng: &mut Rng,
+    ) -> NN {
+        hlayers.retain(|&x| x != 0);
+        hlayers.insert(0, INPUTS);
+        hlayers.push(OUTPUTS);
+        NN::new(hlayers, mut_rate, activ, rng)
+    }
+
+    // Random NEAT genome with the player's inputs wired straight to its outputs
+    pub fn new_genome(mut_rate: f32, activ: ActivationFunc, rng: &mut Rng) -> Genome {
+        Genome::new(INPUTS, OUTPUTS, mut_rate, activ, rng)
+    }
+
     pub fn check_player_collision(&mut self, asteroid: &Asteroid) -> bool {
         // To give more near asteroids data:
 
@@ -150,36 +169,38 @@ impl Player {
             // );
 
             if let Some(brain) = &self.brain {
-                self.outputs = brain.feed_forward(&self.inputs);
+                self.outputs = brain.step(&self.inputs, &mut self.memory);
                 keys = self
                     .outputs
                     .iter()
                     .map(|&x| {
-                        x > if brain.activ_func == ActivationFunc::Sigmoid {
-                            0.85
-                        } else {
-                            0.
+                        x > match brain.out_activ() {
+                            ActivationFunc::Sigmoid => 0.85,
+                            ActivationFunc::Gaussian | ActivationFunc::Step => 0.5,
+                            _ => 0.,
                         }
                     })
                     .collect();
+                for (presses, &key) in self.presses.iter_mut().zip(&keys) {
+                    *presses += key as u32;
+                }
             }
         }
-        if keys[0] || self.brain.is_none() && is_key_down(KeyCode::Right) {
+        if keys[0] || self.brain.is_none() && self.input[0] {
             // RIGHT
             self.rot = (self.rot + 0.1 + TAU as f32) % TAU as f32;
             self.dir = vec2(self.rot.cos(), self.rot.sin());
         }
-        if keys[1] || self.brain.is_none() && is_key_down(KeyCode::Left) {
+        if keys[1] || self.brain.is_none() && self.input[1] {
             // LEFT
             self.rot = (self.rot - 0.1 + TAU as f32) % TAU as f32;
             self.dir = vec2(self.rot.cos(), self.rot.sin());
         }
-        if keys[2] || self.brain.is_none() && is_key_down(KeyCode::Up) {
+        if keys[2] || self.brain.is_none() && self.input[2] {
             // THROTTLE
             self.acc = 0.14;
         }
-        if (keys[3] || self.brain.is_none() && is_key_down(KeyCode::Space))
-            && self.last_shot > self.shot_interval
+        if (keys[3] || self.brain.is_none() && self.input[3]) && self.last_shot > self.shot_interval
         {
             self.last_shot = 0;
             self.shots += 1;
@@ -208,61 +229,10 @@ impl Player {
         // self.asteroid_data.clear();
         // self.raycasts = vec![0.; 8];
     }
-
-    pub fn draw(&self, color: Color, debug: bool) {
-        let p1 = self.pos + self.dir * 20.;
-        let p2 = self.pos + self.dir.rotate(vec2(-18., -12.667));
-        let p3 = self.pos + self.dir.rotate(vec2(-18., 12.667));
-        let p4 = self.pos + self.dir.rotate(vec2(-10., -10.));
-        let p5 = self.pos + self.dir.rotate(vec2(-10., 10.));
-        let p6 = self.pos + self.dir * -25.;
-        let p7 = self.pos + self.dir.rotate(vec2(-10., -6.));
-        let p8 = self.pos + self.dir.rotate(vec2(-10., 6.));
-        draw_line(p1.x, p1.y, p2.x, p2.y, 2., color);
-        draw_line(p1.x, p1.y, p3.x, p3.y, 2., color);
-        draw_line(p4.x, p4.y, p5.x, p5.y, 2., color);
-        if self.acc > 0. && gen_range(0., 1.) < 0.4 {
-            draw_triangle_lines(p6, p7, p8, 2., color);
-        }
-        if debug {
-            if let Some(ast) = self.asteroid.as_ref() {
-                draw_circle_lines(ast.pos.x, ast.pos.y, ast.radius, 1., RED);
-                // let p = self.pos
-                //     + self.dir.rotate(Vec2::from_angle(self.asteroid_data[0].1))
-                //         * self.asteroid_data[0].0
-                //         * WIDTH;
-                draw_line(self.pos.x, self.pos.y, ast.pos.x, ast.pos.y, 1., RED);
-            }
-
-            // Draw raycasts
-
-            // for (i, r) in self.raycasts.iter().enumerate() {
-            //     let dir = Vec2::from_angle(PI / 4. * i as f32).rotate(self.dir);
-            //     draw_line(
-            //         self.pos.x,
-            //         self.pos.y,
-            //         self.pos.x + dir.x * 100. / r,
-            //         self.pos.y + dir.y * 100. / r,
-            //         1.,
-            //         GRAY,
-            //     );
-            // }
-        }
-
-        for bullet in &self.bullets {
-            bullet.draw(color);
-        }
-    }
-
-    pub fn draw_brain(&self, width: f32, height: f32, bias: bool) {
-        if let Some(brain) = &self.brain {
-            brain.draw(width, height, &self.inputs, &self.outputs, bias);
-        }
-    }
 }
 
-struct Bullet {
-    pos: Vec2,
+pub(crate) struct Bullet {
+    pub(crate) pos: Vec2,
     vel: Vec2,
     alive: bool,
 }
@@ -271,7 +241,4 @@ impl Bullet {
     fn update(&mut self) {
         self.pos += self.vel;
     }
-    fn draw(&self, c: Color) {
-        draw_circle(self.pos.x, self.pos.y, 2., Color::new(c.r, c.g, c.b, 0.9));
-    }
 }
@@ -1,7 +1,23 @@
-use macroquad::{prelude::*, rand::gen_range};
+use glam::Vec2;
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    nn::{ActivationFunc, NN},
+    brain::{Brain, BrainKind},
+    config::Config,
+    fitness::FitnessFn,
+    genealogy::{Genealogy, LineageLog},
+    hall_of_fame::HallOfFame,
+    islands::Islands,
+    neat::Innovations,
+    nn::{ActivationFunc, Crossover, Mutation},
+    pareto::{self, Objectives},
+    player::{Player, INPUTS, OUTPUTS},
+    rng::Rng,
+    species::{offspring, speciate, Species},
+    stats::{now, GenStats, StatsLog},
+    stopping::{StopReason, Summary},
     world::World,
 };
 
@@ -11,56 +27,197 @@ pub enum AutoSwitch {
     BestAlive,
 }
 
+// Everything needed to resume training: the brains and asteroid fields of the current
+// generation, the GA settings and the generator state
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    gen: i32,
+    best: f32,
+    #[serde(flatten)]
+    config: Config,
+    rng: Rng,
+    brains: Vec<Brain>,
+    seeds: Vec<u64>,
+    #[serde(default)]
+    history: Vec<GenStats>,
+    #[serde(default)]
+    innovations: Innovations,
+    #[serde(default)]
+    species: Vec<Species>,
+    #[serde(default)]
+    next_species: usize,
+    #[serde(default)]
+    archive: Vec<Vec<f32>>,
+    #[serde(default)]
+    hall: HallOfFame,
+    // Best fitness of any generation and the generation it was scored in
+    #[serde(default)]
+    best_ever: Option<(f32, i32)>,
+    #[serde(default)]
+    champion: Option<Brain>,
+    #[serde(default)]
+    genealogy: Genealogy,
+    #[serde(default)]
+    ids: Vec<u64>,
+    // Island of every ship, empty without islands
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    placement: Vec<usize>,
+}
+
 #[derive(Default)]
 pub struct Population {
     size: usize,
     pub gen: i32,
+    // Best fitness of the last completed generation
+    pub best: f32,
     pub focus: bool,
     pub debug: bool,
     pub worlds: Vec<World>,
     pub track: usize,
-    pub hlayers: Vec<usize>,
+    // Ship carrying on the fittest brain of the last generation, when it was kept
+    pub prev_best: Option<usize>,
+    pub config: Config,
     pub auto_switch: Option<AutoSwitch>,
+    // Stats of every completed generation
+    pub history: Vec<GenStats>,
+    log: Option<StatsLog>,
+    started: f64,
+    rng: Rng,
+    // Numbering of new structure in NEAT genomes
+    innovations: Innovations,
+    // Species of the last completed generation, empty without speciation
+    pub species: Vec<Species>,
+    next_species: usize,
+    // Behaviors novelty is measured against
+    pub archive: Vec<Vec<f32>>,
+    pub hall: HallOfFame,
+    pub genealogy: Genealogy,
+    lineage: Option<LineageLog>,
+    pub best_ever: f32,
+    pub best_gen: i32,
+    // Brain that scored `best_ever`
+    champion: Option<Brain>,
+    // When the run started or was resumed, and why it stopped
+    run_started: f64,
+    pub stopped: Option<StopReason>,
+    // Extra episodes played alongside the worlds, the ones of each brain together
+    episodes: Vec<World>,
+    // Objectives and front of every ship of the last generation, in multi-objective mode
+    pub pareto: Vec<(Objectives, usize)>,
+    // Worlds are stepped on rayon's global pool unless a worker count is set
+    #[cfg(not(target_arch = "wasm32"))]
+    pool: Option<ThreadPool>,
 }
 
 impl Population {
     pub fn new(
         size: usize,
         auto_switch: Option<AutoSwitch>,
-        hlayers: Vec<usize>,
-        mut_rate: f32,
-        activ: ActivationFunc,
+        config: Config,
         (WIDTH, HEIGHT): (f32, f32),
+        mut rng: Rng,
     ) -> Self {
-        let mut s = Self {
-            size,
-            hlayers: hlayers.clone(),
-            worlds: (0..size)
-                .map(|_| {
-                    World::new(
-                        Some(hlayers.clone()),
-                        Some(mut_rate),
-                        Some(activ),
-                        (WIDTH, HEIGHT),
-                    )
-                })
-                .collect(),
+        let scenario = config.common_scenario.then(|| rng.next_u64());
+        let worlds = (0..size)
+            .map(|_| {
+                let brain = match config.brain {
+                    BrainKind::Layered => Brain::NN(Player::new_brain(
+                        config.hlayers.clone(),
+                        config.mut_rate,
+                        config.activ,
+                        &mut rng,
+                    )),
+                    BrainKind::Neat { .. } => {
+                        Brain::Neat(Player::new_genome(config.mut_rate, config.activ, &mut rng))
+                    }
+                };
+                let seed = scenario.unwrap_or_else(|| rng.next_u64());
+                World::simulate(brain, (WIDTH, HEIGHT), seed)
+            })
+            .collect();
+        let mut s = Self::with_worlds(worlds, auto_switch, config, rng);
+        if s.config.recurrent {
+            for world in &mut s.worlds {
+                if let Some(Brain::NN(nn)) = &mut world.player.brain {
+                    nn.add_recurrence(&mut s.rng);
+                }
+            }
+        }
+        s.change_mutation(s.config.mutation);
+        s.change_layer_activs(s.config.hidden_activs.clone(), s.config.out_activ);
+        s.start((WIDTH, HEIGHT));
+        s
+    }
+
+    // A first generation of the given brains, unchanged
+    pub fn seeded(
+        brains: Vec<Brain>,
+        auto_switch: Option<AutoSwitch>,
+        config: Config,
+        (WIDTH, HEIGHT): (f32, f32),
+        mut rng: Rng,
+    ) -> Self {
+        let scenario = config.common_scenario.then(|| rng.next_u64());
+        let worlds = brains
+            .into_iter()
+            .map(|brain| {
+                let seed = scenario.unwrap_or_else(|| rng.next_u64());
+                World::simulate(brain, (WIDTH, HEIGHT), seed)
+            })
+            .collect();
+        let mut s = Self::with_worlds(worlds, auto_switch, config, rng);
+        for world in &s.worlds {
+            if let Some(Brain::Neat(genome)) = &world.player.brain {
+                s.innovations.cover(genome);
+            }
+        }
+        s.start((WIDTH, HEIGHT));
+        s
+    }
+
+    fn with_worlds(
+        worlds: Vec<World>,
+        auto_switch: Option<AutoSwitch>,
+        config: Config,
+        rng: Rng,
+    ) -> Self {
+        Self {
+            size: worlds.len(),
+            worlds,
+            innovations: Innovations::new(INPUTS, OUTPUTS),
+            config,
             auto_switch,
             focus: true,
+            started: now(),
+            run_started: now(),
+            rng,
             ..Default::default()
-        };
-        s.worlds[0].track(true);
-        s
+        }
     }
 
-    pub fn update(&mut self, (WIDTH, HEIGHT): (f32, f32)) {
-        let mut alive = false;
-        for world in &mut self.worlds {
-            if !world.over {
-                alive = true;
-                world.update((WIDTH, HEIGHT));
-            }
+    // Deals the first generation to islands, starts its lines and readies it to play
+    fn start(&mut self, (WIDTH, HEIGHT): (f32, f32)) {
+        let islands = self.islands();
+        for (i, world) in self.worlds.iter_mut().enumerate() {
+            world.island = i % islands.count;
+            world.id = self.genealogy.birth(vec![], 0, 0);
         }
+        self.change_fitness(self.config.fitness);
+        if self.config.common_scenario {
+            self.worlds.iter_mut().for_each(World::share_spawns);
+        }
+        self.episodes = self.episode_worlds((WIDTH, HEIGHT));
+        self.worlds[0].track(true);
+    }
+
+    pub fn update(&mut self, (WIDTH, HEIGHT): (f32, f32)) {
+        let alive = self.worlds.iter().chain(&self.episodes).any(|w| !w.over);
+        let mut worlds = std::mem::take(&mut self.worlds);
+        self.step(&mut worlds, (WIDTH, HEIGHT));
+        self.worlds = worlds;
+        let mut episodes = std::mem::take(&mut self.episodes);
+        self.step(&mut episodes, (WIDTH, HEIGHT));
+        self.episodes = episodes;
         if self.worlds[self.track].over {
             if let Some(auto_switch) = self.auto_switch {
                 match auto_switch {
@@ -75,6 +232,36 @@ impl Population {
         }
     }
 
+    // Number of threads used to step the worlds, 0 to use all cores
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_threads(&mut self, threads: usize) {
+        self.pool = match threads {
+            0 => None,
+            n => Some(
+                ThreadPoolBuilder::new()
+                    .num_threads(n)
+                    .build()
+                    .expect("Unable to create thread pool"),
+            ),
+        };
+    }
+
+    // Every world owns its generator, so stepping them in any order gives the same result
+    fn step(&self, worlds: &mut [World], (WIDTH, HEIGHT): (f32, f32)) {
+        let step = |world: &mut World| {
+            if !world.over {
+                world.update((WIDTH, HEIGHT));
+            }
+        };
+        #[cfg(not(target_arch = "wasm32"))]
+        match &self.pool {
+            Some(pool) => pool.install(|| worlds.par_iter_mut().for_each(step)),
+            None => worlds.par_iter_mut().for_each(step),
+        }
+        #[cfg(target_arch = "wasm32")]
+        worlds.iter_mut().for_each(step);
+    }
+
     pub fn change_track(&mut self, pos: Vec2) {
         for i in 0..self.worlds.len() {
             if !self.worlds[i].over
@@ -103,80 +290,667 @@ impl Population {
         }
     }
     pub fn track_prev_best(&mut self) {
-        self.worlds[self.track].track(false);
-        self.worlds[0].track(true);
-        self.track = 0;
+        if let Some(i) = self.prev_best {
+            self.worlds[self.track].track(false);
+            self.worlds[i].track(true);
+            self.track = i;
+        }
+    }
+
+    pub fn save(&self, path: &str) {
+        let checkpoint = Checkpoint {
+            gen: self.gen,
+            best: self.best,
+            config: self.config.clone(),
+            rng: self.rng.clone(),
+            brains: self.worlds.iter().map(|w| w.see_brain().clone()).collect(),
+            seeds: self.worlds.iter().map(|w| w.seed).collect(),
+            history: self.history.clone(),
+            innovations: self.innovations.clone(),
+            species: self.species.clone(),
+            next_species: self.next_species,
+            archive: self.archive.clone(),
+            hall: self.hall.clone(),
+            best_ever: Some((self.best_ever, self.best_gen)),
+            champion: self.champion.clone(),
+            genealogy: self.genealogy.clone(),
+            ids: self.worlds.iter().map(|w| w.id).collect(),
+            placement: match self.config.islands {
+                Some(_) => self.worlds.iter().map(|w| w.island).collect(),
+                None => vec![],
+            },
+        };
+        let json = serde_json::to_string(&checkpoint).unwrap();
+        std::fs::write(path, json).expect("Unable to write file");
+    }
+
+    // Restarts the saved generation, which replays exactly as it would have without the save
+    pub fn load(path: &str, auto_switch: Option<AutoSwitch>, (WIDTH, HEIGHT): (f32, f32)) -> Self {
+        let json = std::fs::read_to_string(path).expect("Unable to read file");
+        let checkpoint: Checkpoint = serde_json::from_str(&json).unwrap();
+        let mut s = Self {
+            size: checkpoint.brains.len(),
+            gen: checkpoint.gen,
+            best: checkpoint.best,
+            config: checkpoint.config,
+            rng: checkpoint.rng,
+            worlds: checkpoint
+                .brains
+                .into_iter()
+                .zip(checkpoint.seeds)
+                .map(|(brain, seed)| World::simulate(brain, (WIDTH, HEIGHT), seed))
+                .collect(),
+            history: checkpoint.history,
+            innovations: checkpoint.innovations,
+            species: checkpoint.species,
+            next_species: checkpoint.next_species,
+            archive: checkpoint.archive,
+            hall: checkpoint.hall,
+            genealogy: checkpoint.genealogy,
+            best_ever: checkpoint.best_ever.map_or(checkpoint.best, |b| b.0),
+            best_gen: checkpoint.best_ever.map_or(checkpoint.gen, |b| b.1),
+            champion: checkpoint.champion,
+            run_started: now(),
+            auto_switch,
+            focus: true,
+            started: now(),
+            ..Default::default()
+        };
+        for (world, &island) in s.worlds.iter_mut().zip(&checkpoint.placement) {
+            world.island = island;
+        }
+        // Checkpoints saved before genealogy start new lines
+        for (i, world) in s.worlds.iter_mut().enumerate() {
+            world.id = match checkpoint.ids.get(i) {
+                Some(&id) => id,
+                None => s.genealogy.birth(vec![], s.gen, 0),
+            };
+        }
+        s.change_fitness(s.config.fitness);
+        if s.config.common_scenario {
+            s.worlds.iter_mut().for_each(World::share_spawns);
+        }
+        s.episodes = s.episode_worlds((WIDTH, HEIGHT));
+        s.worlds[0].track(true);
+        s
+    }
+
+    // Writes every brain of every following generation with its parents to a file
+    pub fn lineage_to(&mut self, path: &str) {
+        self.lineage = Some(LineageLog::create(path));
+    }
+
+    // Appends the stats of every following generation to a file
+    pub fn log_to(&mut self, path: &str) {
+        self.log = Some(StatsLog::create(path));
     }
 
     pub fn change_mut(&mut self, mut_rate: f32) {
+        self.config.mut_rate = mut_rate;
         for world in &mut self.worlds {
-            world.player.brain.as_mut().unwrap().mut_rate = mut_rate;
+            world.player.brain.as_mut().unwrap().set_mut_rate(mut_rate);
+        }
+    }
+
+    pub fn change_mutation(&mut self, mutation: Mutation) {
+        self.config.mutation = mutation;
+        if let Mutation::Gaussian { sigma } | Mutation::Scaled { sigma } = mutation {
+            for world in &mut self.worlds {
+                world.player.brain.as_mut().unwrap().set_sigma(sigma);
+            }
         }
     }
 
     pub fn change_activ(&mut self, activ: ActivationFunc) {
+        self.config.activ = activ;
+        for world in &mut self.worlds {
+            world.player.brain.as_mut().unwrap().set_activ(activ);
+        }
+    }
+
+    pub fn change_layer_activs(
+        &mut self,
+        hidden_activs: Vec<Option<ActivationFunc>>,
+        out_activ: Option<ActivationFunc>,
+    ) {
+        self.config.hidden_activs = hidden_activs;
+        self.config.out_activ = out_activ;
+        let layer_activs = self.config.layer_activs();
         for world in &mut self.worlds {
-            world.player.brain.as_mut().unwrap().activ_func = activ;
+            world
+                .player
+                .brain
+                .as_mut()
+                .unwrap()
+                .set_layer_activs(layer_activs.clone());
+        }
+    }
+
+    pub fn change_fitness(&mut self, fitness: FitnessFn) {
+        self.config.fitness = fitness;
+        for world in self.worlds.iter_mut().chain(&mut self.episodes) {
+            world.fitness_fn = fitness;
+        }
+    }
+
+    fn simulate(
+        &self,
+        brain: Brain,
+        island: usize,
+        (WIDTH, HEIGHT): (f32, f32),
+        seed: u64,
+    ) -> World {
+        let mut world = World::simulate(brain, (WIDTH, HEIGHT), seed);
+        world.fitness_fn = self.config.fitness;
+        world.island = island;
+        if self.config.common_scenario {
+            world.share_spawns();
         }
+        world
     }
 
-    pub fn draw(&self, (WIDTH, HEIGHT, SWIDTH, SHEIGHT): (f32, f32, f32, f32)) {
-        for world in self.worlds.iter().rev() {
-            if self.focus {
-                if world.track {
-                    world.draw(self.debug);
+    // Extra episodes of every world, played on fields derived from its seed so they are the
+    // same whether they run in parallel or after it
+    fn episode(&self, world: &World, episode: u32, (WIDTH, HEIGHT): (f32, f32)) -> World {
+        let seed = Rng::new(world.seed, episode as u64).next_u64();
+        let brain = world.see_brain().clone();
+        self.simulate(brain, world.island, (WIDTH, HEIGHT), seed)
+    }
+
+    fn episode_worlds(&self, (WIDTH, HEIGHT): (f32, f32)) -> Vec<World> {
+        if !self.config.parallel_episodes {
+            return vec![];
+        }
+        self.worlds
+            .iter()
+            .flat_map(|w| (1..self.config.episodes).map(move |e| (w, e)))
+            .map(|(w, e)| self.episode(w, e, (WIDTH, HEIGHT)))
+            .collect()
+    }
+
+    // Combines the scores of every brain over all its episodes, playing the ones that did
+    // not run in parallel first, one episode of all brains at a time
+    fn score_episodes(&mut self, (WIDTH, HEIGHT): (f32, f32)) {
+        let extra = self.config.episodes.max(1) as usize - 1;
+        if extra == 0 {
+            return;
+        }
+        let mut runs = vec![vec![]; self.worlds.len()];
+        if self.episodes.len() == self.worlds.len() * extra {
+            for (runs, episodes) in runs.iter_mut().zip(self.episodes.chunks(extra)) {
+                runs.extend(episodes.iter().map(|w| (w.fitness, w.objectives())));
+            }
+        } else {
+            for e in 1..=extra as u32 {
+                let mut worlds = self
+                    .worlds
+                    .iter()
+                    .map(|w| self.episode(w, e, (WIDTH, HEIGHT)))
+                    .collect::<Vec<_>>();
+                while worlds.iter().any(|w| !w.over) {
+                    self.step(&mut worlds, (WIDTH, HEIGHT));
+                }
+                for (runs, w) in runs.iter_mut().zip(&worlds) {
+                    runs.push((w.fitness, w.objectives()));
                 }
-            } else if !world.over {
-                world.draw(self.debug);
             }
         }
-        self.draw_borders((WIDTH, HEIGHT, SWIDTH, SHEIGHT));
+        self.episodes.clear();
+        let aggregate = self.config.aggregate;
+        for (world, mut runs) in self.worlds.iter_mut().zip(runs) {
+            runs.push((world.fitness, world.objectives()));
+            world.fitness = aggregate.apply(runs.iter().map(|r| r.0).collect());
+            world.scored = Some(std::array::from_fn(|m| {
+                aggregate.apply(runs.iter().map(|r| r.1[m]).collect())
+            }));
+        }
     }
 
-    pub fn draw_borders(&self, (WIDTH, HEIGHT, SWIDTH, SHEIGHT): (f32, f32, f32, f32)) {
-        let th = (SHEIGHT - HEIGHT) * 0.5;
-        draw_rectangle(-WIDTH * 0.5, -SHEIGHT * 0.5, WIDTH, th, BLACK);
-        draw_rectangle(-WIDTH * 0.5, SHEIGHT * 0.5 - th, WIDTH, th, BLACK);
-        draw_rectangle(-WIDTH * 0.5 - th, -SHEIGHT * 0.5, th, SHEIGHT, BLACK);
-        draw_rectangle(WIDTH * 0.5, -SHEIGHT * 0.5, SWIDTH - WIDTH, SHEIGHT, BLACK);
+    // Islands of the population, a single one without islands. There are never more
+    // islands than ships.
+    pub fn islands(&self) -> Islands {
+        let islands = self.config.islands.unwrap_or(Islands {
+            count: 1,
+            ..Default::default()
+        });
+        Islands {
+            count: islands.count.clamp(1, self.size.max(1)),
+            ..islands
+        }
+    }
+
+    // Replays the given candidates on new asteroid fields and gives them their mean fitness
+    fn reevaluate(&mut self, candidates: &[usize], (WIDTH, HEIGHT): (f32, f32)) {
+        let runs = self.config.elite_evals as usize - 1;
+        // With a common scenario every candidate replays the same fields
+        let scenarios = match self.config.common_scenario {
+            true => (0..runs).map(|_| self.rng.next_u64()).collect(),
+            false => vec![],
+        };
+        let mut worlds = (0..candidates.len() * runs)
+            .map(|i| {
+                let world = &self.worlds[candidates[i / runs]];
+                let seed = match scenarios.get(i % runs) {
+                    Some(&seed) => seed,
+                    None => self.rng.next_u64(),
+                };
+                self.simulate(
+                    world.see_brain().clone(),
+                    world.island,
+                    (WIDTH, HEIGHT),
+                    seed,
+                )
+            })
+            .collect::<Vec<_>>();
+        while worlds.iter().any(|w| !w.over) {
+            self.step(&mut worlds, (WIDTH, HEIGHT));
+        }
+        for (&i, runs) in candidates.iter().zip(worlds.chunks(runs)) {
+            let total = runs
+                .iter()
+                .fold(self.worlds[i].fitness, |acc, w| acc + w.fitness);
+            self.worlds[i].fitness = total / (runs.len() + 1) as f32;
+        }
+    }
+
+    // Ships of every island, fittest first
+    fn members(&self, islands: usize) -> Vec<Vec<usize>> {
+        (0..islands)
+            .map(|island| {
+                (0..self.worlds.len())
+                    .filter(|&i| self.worlds[i].island == island)
+                    .collect()
+            })
+            .collect()
     }
 
     pub fn next_gen(&mut self, (WIDTH, HEIGHT): (f32, f32)) {
-        let total = self.worlds.iter().fold(0., |acc, x| acc + x.fitness);
-        self.worlds
-            .sort_by(|a, b| b.fitness.partial_cmp(&a.fitness).unwrap());
+        self.score_episodes((WIDTH, HEIGHT));
+        // Worlds are sorted by the crowded comparison in multi-objective mode, which then
+        // replaces fitness in selection
+        let mut scores: Option<Vec<f32>> = None;
+        if self.config.multi_objective {
+            let points = self
+                .worlds
+                .iter()
+                .map(|w| w.objectives())
+                .collect::<Vec<_>>();
+            let (ranks, nsga) = pareto::rank(&points);
+            self.pareto = points.into_iter().zip(ranks).collect();
+            let mut ranked = std::mem::take(&mut self.worlds)
+                .into_iter()
+                .zip(nsga)
+                .collect::<Vec<_>>();
+            ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+            let (worlds, nsga) = ranked.into_iter().unzip();
+            self.worlds = worlds;
+            scores = Some(nsga);
+        } else {
+            self.pareto.clear();
+            self.worlds
+                .sort_by(|a, b| b.fitness.partial_cmp(&a.fitness).unwrap());
+        }
+        let islands = self.islands();
+        let mut members = self.members(islands.count);
+        let elites = members
+            .iter()
+            .enumerate()
+            .map(|(island, members)| match self.config.elitism {
+                f if f > 0. => {
+                    let share = islands.share(self.size, island);
+                    ((share as f32 * f) as usize)
+                        .clamp(1, share)
+                        .min(members.len())
+                }
+                _ => 0,
+            })
+            .collect::<Vec<_>>();
+        // Twice as many candidates as elites are replayed and elites are picked by their mean
+        // fitness, so a lucky field does not keep a weak brain on top. Averaging only smooths
+        // fitness, which does not rank ships in multi-objective mode.
+        let mut candidate = vec![true; self.worlds.len()];
+        if self.config.elite_evals > 1 && !self.config.multi_objective {
+            let pool = members
+                .iter()
+                .zip(&elites)
+                .flat_map(|(members, &elites)| members.iter().take(2 * elites).copied())
+                .collect::<Vec<_>>();
+            self.reevaluate(&pool, (WIDTH, HEIGHT));
+            candidate = (0..self.worlds.len()).map(|i| pool.contains(&i)).collect();
+            let mut worlds = std::mem::take(&mut self.worlds)
+                .into_iter()
+                .zip(candidate)
+                .collect::<Vec<_>>();
+            worlds.sort_by(|a, b| b.0.fitness.partial_cmp(&a.0.fitness).unwrap());
+            (self.worlds, candidate) = worlds.into_iter().unzip();
+            members = self.members(islands.count);
+        }
+        let chosen = members
+            .iter()
+            .zip(&elites)
+            .map(|(members, &elites)| {
+                let candidates = members.iter().filter(|&&i| candidate[i]);
+                candidates.take(elites).copied().collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
         // for i in &self.worlds {
         //     println!("Fitness: {}", i.fitness);
         // }
-        println!("Gen: {}, Fitness: {}", self.gen, self.worlds[0].fitness);
-        let mut new_worlds = (0..std::cmp::max(1, self.size / 20))
-            .map(|i| World::simulate(self.worlds[i].see_brain().to_owned(), (WIDTH, HEIGHT)))
+        self.best = self
+            .worlds
+            .iter()
+            .map(|w| w.fitness)
+            .fold(f32::MIN, f32::max);
+        println!("Gen: {}, Fitness: {}", self.gen, self.best);
+        if self.best > self.best_ever || self.champion.is_none() {
+            (self.best_ever, self.best_gen) = (self.best, self.gen);
+            let best = self.worlds.iter().find(|w| w.fitness == self.best);
+            self.champion = best.map(|w| w.see_brain().clone());
+        }
+        let scored = self
+            .worlds
+            .iter()
+            .map(|w| (w.id, w.fitness))
             .collect::<Vec<_>>();
-        while new_worlds.len() < self.size {
-            let rands = (gen_range(0., total), gen_range(0., total));
-            let mut sum = 0.;
-            let (mut a, mut b) = (None, None);
-            for world in &self.worlds {
-                sum += world.fitness;
-                if a.is_none() && sum >= rands.0 {
-                    a = Some(world.see_brain());
-                }
-                if b.is_none() && sum >= rands.1 {
-                    b = Some(world.see_brain());
+        for &(id, fitness) in &scored {
+            self.genealogy.score(id, fitness);
+        }
+        if let Some(lineage) = &mut self.lineage {
+            lineage.write(self.gen, &scored, &self.genealogy);
+        }
+        self.hall
+            .add(&self.worlds, self.gen, self.config.hall_of_fame);
+        let stats = GenStats::new(self.gen, &self.worlds, (now() - self.started) as f32);
+        if let Some(log) = &mut self.log {
+            log.write(&stats);
+        }
+        self.history.push(stats);
+        let mut fitness = scores.unwrap_or_else(|| self.worlds.iter().map(|w| w.fitness).collect());
+        if let Some(novelty) = self.config.novelty {
+            let behaviors = self.worlds.iter().map(|w| w.behavior()).collect::<Vec<_>>();
+            fitness = novelty.score(&fitness, &behaviors, &mut self.archive);
+        }
+        let speciation = self.config.speciation || self.config.brain != BrainKind::Layered;
+        if speciation {
+            let threshold = self.config.species_threshold();
+            speciate(
+                &mut self.species,
+                &self.worlds,
+                threshold,
+                &mut self.next_species,
+            );
+        } else {
+            self.species.clear();
+        }
+        let growth = match self.config.brain {
+            BrainKind::Layered => (0., 0.),
+            BrainKind::Neat { add_conn, add_node } => (add_conn, add_node),
+        };
+        self.innovations.clear();
+        let migrate =
+            islands.count > 1 && islands.interval > 0 && self.gen % islands.interval as i32 == 0;
+        // Seed of the asteroid field shared by the whole next generation
+        let scenario = self.config.common_scenario.then(|| self.rng.next_u64());
+        let top = (0..self.worlds.len())
+            .max_by(|&a, &b| self.worlds[a].fitness.total_cmp(&self.worlds[b].fitness));
+        let mut prev_best = None;
+        let mut new_worlds = vec![];
+        for (island, own) in members.iter().enumerate() {
+            if own.is_empty() {
+                continue;
+            }
+            let share = islands.share(self.size, island);
+            // Best ships of the neighbouring islands take the places of children
+            let migrants = match migrate {
+                true => islands
+                    .sources(island)
+                    .iter()
+                    .flat_map(|&source| members[source].iter().take(islands.migrants))
+                    .take(share - elites[island])
+                    .copied()
+                    .collect::<Vec<_>>(),
+                false => vec![],
+            };
+            for &i in chosen[island].iter().chain(&migrants) {
+                if Some(i) == top && prev_best.is_none() {
+                    prev_best = Some(new_worlds.len());
                 }
+                let seed = scenario.unwrap_or_else(|| self.rng.next_u64());
+                let brain = self.worlds[i].see_brain().to_owned();
+                let mut world = self.simulate(brain, island, (WIDTH, HEIGHT), seed);
+                world.id = self.worlds[i].id;
+                new_worlds.push(world);
             }
-            if a.is_none() {
-                a = Some(self.worlds.last().unwrap().see_brain());
+            // Brains from the hall of fame rejoin, dealt to islands in turn
+            let mut children = share - elites[island] - migrants.len();
+            if !self.hall.entries.is_empty() {
+                let reinjected = (0..self.config.reinject)
+                    .filter(|j| j % islands.count == island)
+                    .count()
+                    .min(children);
+                for _ in 0..reinjected {
+                    let entry = self.rng.gen_range(0, self.hall.entries.len());
+                    let brain = self.hall.entries[entry].brain.clone();
+                    let seed = scenario.unwrap_or_else(|| self.rng.next_u64());
+                    let mut world = self.simulate(brain, island, (WIDTH, HEIGHT), seed);
+                    world.id = self.hall.entries[entry].id;
+                    new_worlds.push(world);
+                }
+                children -= reinjected;
             }
-            if b.is_none() {
-                b = Some(self.worlds.last().unwrap().see_brain());
+            // Parents are picked within a species, which gets children for its mean fitness.
+            // Stagnant species get no children, unless they hold the best ship of the island.
+            let groups = match speciation {
+                true => {
+                    let stagnation = self.config.stagnation;
+                    self.species
+                        .iter()
+                        .map(|s| {
+                            let group = s
+                                .members
+                                .iter()
+                                .filter(|&&i| self.worlds[i].island == island);
+                            (s.stale, group.copied().collect::<Vec<_>>())
+                        })
+                        .filter(|(stale, group)| {
+                            !group.is_empty()
+                                && (stagnation == 0 || *stale < stagnation || group[0] == own[0])
+                        })
+                        .map(|(_, group)| group)
+                        .collect::<Vec<_>>()
+                }
+                false => vec![own.clone()],
+            };
+            let counts = offspring(&groups, &fitness, children);
+            for (members, count) in groups.iter().zip(counts) {
+                let fitness = members.iter().map(|&i| fitness[i]).collect::<Vec<_>>();
+                let parents = self
+                    .config
+                    .selection
+                    .select(&fitness, 2 * count, &mut self.rng);
+                for pair in parents.chunks(2) {
+                    // Worlds are sorted, so the lower index is the fitter parent
+                    let (a, b) = (members[pair[0]], members[pair[1]]);
+                    let (a, b) = (a.min(b), a.max(b));
+                    let mut new_brain = Brain::crossover(
+                        self.worlds[a].see_brain(),
+                        self.worlds[b].see_brain(),
+                        self.config.crossover,
+                        &mut self.rng,
+                    );
+                    if self.config.self_adaptive {
+                        new_brain.adapt(&mut self.rng);
+                    }
+                    let mutations = match &mut new_brain {
+                        Brain::NN(nn) => nn.mutate(self.config.mutation, &mut self.rng),
+                        Brain::Neat(genome) => genome.mutate(
+                            self.config.mutation,
+                            growth,
+                            &mut self.innovations,
+                            &mut self.rng,
+                        ),
+                    };
+                    // Without crossover only the fitter parent passes anything on
+                    let mut parents = vec![self.worlds[a].id, self.worlds[b].id];
+                    if a == b || self.config.crossover == Crossover::None {
+                        parents.truncate(1);
+                    }
+                    let seed = scenario.unwrap_or_else(|| self.rng.next_u64());
+                    let mut world = self.simulate(new_brain, island, (WIDTH, HEIGHT), seed);
+                    world.id = self.genealogy.birth(parents, self.gen, mutations);
+                    new_worlds.push(world);
+                }
             }
-            let mut new_brain = NN::crossover(a.unwrap(), b.unwrap());
-            new_brain.mutate();
-            new_worlds.push(World::simulate(new_brain, (WIDTH, HEIGHT)));
         }
         self.worlds = new_worlds;
+        self.prev_best = prev_best;
+        let hall = self.hall.entries.iter().map(|e| e.id);
+        self.genealogy
+            .prune(self.worlds.iter().map(|w| w.id).chain(hall));
+        self.episodes = self.episode_worlds((WIDTH, HEIGHT));
         self.worlds[0].track(true);
         self.track = 0;
+        self.started = now();
+        if self.stopped.is_none() {
+            self.stopped = self.stop_reason();
+        }
+    }
+
+    // First stop condition the run meets
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        let stop = self.config.stop;
+        if stop.target.is_some_and(|t| self.best >= t) {
+            Some(StopReason::Target)
+        } else if stop.generations.is_some_and(|n| self.gen >= n) {
+            Some(StopReason::Generations)
+        } else if stop.patience.is_some_and(|n| self.gen - self.best_gen >= n) {
+            Some(StopReason::Patience)
+        } else if stop
+            .seconds
+            .is_some_and(|t| now() > 0. && (now() - self.run_started) as f32 >= t)
+        {
+            Some(StopReason::Time)
+        } else {
+            None
+        }
+    }
+
+    // Fittest brain ever seen. Checkpoints saved before it was kept fall back to the top of
+    // the hall of fame, and a run that has not finished a generation to its first ship.
+    pub fn best_brain(&self) -> &Brain {
+        match (&self.champion, self.hall.entries.first()) {
+            (Some(brain), _) => brain,
+            (None, Some(entry)) => &entry.brain,
+            (None, None) => self.worlds[0].see_brain(),
+        }
+    }
+
+    pub fn summary(&self) -> Summary<'_> {
+        Summary {
+            reason: self.stopped,
+            gen: self.gen,
+            best: self.best,
+            best_ever: self.best_ever,
+            best_gen: self.best_gen,
+            seconds: (now() - self.run_started) as f32,
+            last: self.history.last(),
+            config: &self.config,
+        }
+    }
+
+    // Saves the best brain and the summary of the run
+    pub fn save_results(&self, model: &str, summary: &str) {
+        std::fs::write(model, self.best_brain().export()).expect("Unable to write file");
+        self.summary().save(summary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    const DIMS: (f32, f32) = (800., 600.);
+
+    #[test]
+    fn seeded_genomes_get_new_innovations() {
+        let mut rng = Rng::new(3, 0);
+        let mut innovations = Innovations::new(INPUTS, OUTPUTS);
+        let mut genome = Player::new_genome(0.1, ActivationFunc::ReLU, &mut rng);
+        for _ in 0..40 {
+            innovations.clear();
+            genome.mutate(Mutation::Reset, (0.8, 0.5), &mut innovations, &mut rng);
+        }
+        assert!(!genome.hidden.is_empty());
+        let config = Config {
+            brain: BrainKind::Neat {
+                add_conn: 0.8,
+                add_node: 0.5,
+            },
+            ..Default::default()
+        };
+        let brains = vec![Brain::Neat(genome); 30];
+        let mut pop = Population::seeded(brains, None, config, DIMS, rng);
+        for _ in 0..3 {
+            pop.gen += 1;
+            pop.next_gen(DIMS);
+        }
+        for world in &pop.worlds {
+            let Brain::Neat(genome) = world.see_brain() else {
+                unreachable!()
+            };
+            let mut seen = HashSet::new();
+            assert!(genome.conns.iter().all(|c| seen.insert(c.innovation)));
+            let mut seen = HashSet::new();
+            assert!(genome.hidden.iter().all(|&n| seen.insert(n)));
+        }
+    }
+
+    #[test]
+    fn best_brain_is_the_fittest_ever_scored() {
+        let config = Config {
+            hall_of_fame: 0,
+            elitism: 0.,
+            ..Default::default()
+        };
+        let mut pop = Population::new(20, None, config, DIMS, Rng::new(4, 0));
+        for (i, world) in pop.worlds.iter_mut().enumerate() {
+            world.fitness = (i * 7 % 20) as f32;
+        }
+        let best = pop.worlds[17].see_brain().export();
+        pop.gen += 1;
+        pop.next_gen(DIMS);
+        assert_eq!(pop.best_brain().export(), best);
+        // A worse generation keeps it
+        pop.gen += 1;
+        pop.next_gen(DIMS);
+        assert_eq!(pop.best_brain().export(), best);
+    }
+
+    #[test]
+    fn prev_best_follows_the_fittest_elite() {
+        for (elitism, kept) in [(0.1, true), (0., false)] {
+            let config = Config {
+                elitism,
+                islands: Some(Islands {
+                    count: 3,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            let mut pop = Population::new(30, None, config, DIMS, Rng::new(9, 0));
+            for (i, world) in pop.worlds.iter_mut().enumerate() {
+                world.fitness = (i * 11 % 30) as f32;
+            }
+            let best = pop.worlds[19].see_brain().export();
+            pop.gen += 1;
+            pop.next_gen(DIMS);
+            match pop.prev_best {
+                Some(i) => assert_eq!(pop.worlds[i].see_brain().export(), best),
+                None => assert!(!kept),
+            }
+            assert_eq!(pop.prev_best.is_some(), kept);
+        }
     }
 }
@@ -0,0 +1,543 @@
+// Drawing of the simulation with macroquad, only built with the "render" feature.
+// The simulation modules themselves never draw or read input.
+
+use macroquad::{prelude::*, rand::gen_range};
+
+use crate::{
+    asteroids::{Asteroid, AsteroidSize},
+    brain::Brain,
+    neat::Genome,
+    nn::NN,
+    player::{Bullet, Player},
+    population::Population,
+    world::World,
+};
+
+impl Population {
+    pub fn draw(&self, (WIDTH, HEIGHT, SWIDTH, SHEIGHT): (f32, f32, f32, f32)) {
+        for world in self.worlds.iter().rev() {
+            if self.focus {
+                if world.track {
+                    world.draw(self.debug);
+                }
+            } else if !world.over {
+                world.draw(self.debug);
+            }
+        }
+        self.draw_borders((WIDTH, HEIGHT, SWIDTH, SHEIGHT));
+    }
+
+    pub fn draw_borders(&self, (WIDTH, HEIGHT, SWIDTH, SHEIGHT): (f32, f32, f32, f32)) {
+        let th = (SHEIGHT - HEIGHT) * 0.5;
+        draw_rectangle(-WIDTH * 0.5, -SHEIGHT * 0.5, WIDTH, th, BLACK);
+        draw_rectangle(-WIDTH * 0.5, SHEIGHT * 0.5 - th, WIDTH, th, BLACK);
+        draw_rectangle(-WIDTH * 0.5 - th, -SHEIGHT * 0.5, th, SHEIGHT, BLACK);
+        draw_rectangle(WIDTH * 0.5, -SHEIGHT * 0.5, SWIDTH - WIDTH, SHEIGHT, BLACK);
+    }
+
+    // Best and mean fitness of every completed generation
+    pub fn draw_history(&self, width: f32, height: f32, log: bool) {
+        draw_rectangle_lines(-width * 0.5, -height * 0.5, width, height, 2., WHITE);
+        if self.history.len() < 2 {
+            let str = "Waiting for generations...";
+            let w = measure_text(str, None, 16, 1.);
+            draw_text(str, -w.width * 0.5, 4., 16., GRAY);
+            return;
+        }
+        // log(1 + x) so that zero fitness stays on the chart
+        let scale = |x: f32| if log { x.max(0.).ln_1p() } else { x };
+        let max = self
+            .history
+            .iter()
+            .map(|s| s.best)
+            .fold(f32::EPSILON, f32::max);
+
+        let (left, top) = (-width * 0.5 + 60., -height * 0.5 + 30.);
+        let (w, h) = (width - 80., height - 55.);
+        let point = |i: usize, x: f32| {
+            vec2(
+                left + w * i as f32 / (self.history.len() - 1) as f32,
+                top + h * (1. - scale(x) / scale(max)),
+            )
+        };
+        draw_line(left, top, left, top + h, 1., GRAY);
+        draw_line(left, top + h, left + w, top + h, 1., GRAY);
+        for (i, pair) in self.history.windows(2).enumerate() {
+            let (a, b) = (point(i, pair[0].mean), point(i + 1, pair[1].mean));
+            draw_line(a.x, a.y, b.x, b.y, 1.5, WHITE);
+            let (a, b) = (point(i, pair[0].best), point(i + 1, pair[1].best));
+            draw_line(a.x, a.y, b.x, b.y, 1.5, GREEN);
+        }
+
+        draw_text(
+            &format!("{:.0}", max),
+            -width * 0.5 + 8.,
+            top + 4.,
+            16.,
+            WHITE,
+        );
+        draw_text("0", -width * 0.5 + 8., top + h + 4., 16., WHITE);
+        let first = self.history[0].gen.to_string();
+        draw_text(&first, left, top + h + 16., 16., WHITE);
+        let last = self.history.last().unwrap().gen.to_string();
+        let m = measure_text(&last, None, 16, 1.);
+        draw_text(&last, left + w - m.width, top + h + 16., 16., WHITE);
+        draw_text(
+            if log { "Fitness (log)" } else { "Fitness" },
+            left,
+            top - 12.,
+            16.,
+            WHITE,
+        );
+        draw_rectangle(left + w - 140., top - 20., 10., 10., GREEN);
+        draw_text("Best", left + w - 125., top - 11., 16., WHITE);
+        draw_rectangle(left + w - 70., top - 20., 10., 10., WHITE);
+        draw_text("Mean", left + w - 55., top - 11., 16., WHITE);
+    }
+
+    // Lifespan against kills of every ship of the last generation, brighter and bigger for
+    // better accuracy, with the first front in green
+    pub fn draw_front(&self, width: f32, height: f32) {
+        draw_rectangle_lines(-width * 0.5, -height * 0.5, width, height, 2., WHITE);
+        if self.pareto.is_empty() {
+            let str = "Waiting for a multi-objective generation...";
+            let w = measure_text(str, None, 16, 1.);
+            draw_text(str, -w.width * 0.5, 4., 16., GRAY);
+            return;
+        }
+        let max = self.pareto.iter().fold([f32::EPSILON; 3], |m, (p, _)| {
+            [m[0].max(p[0]), m[1].max(p[1]), m[2].max(p[2])]
+        });
+        let (left, top) = (-width * 0.5 + 60., -height * 0.5 + 30.);
+        let (w, h) = (width - 80., height - 55.);
+        draw_line(left, top, left, top + h, 1., GRAY);
+        draw_line(left, top + h, left + w, top + h, 1., GRAY);
+        // Dominated ships first so the front is drawn over them
+        for (p, front) in self
+            .pareto
+            .iter()
+            .filter(|(_, f)| *f > 0)
+            .chain(self.pareto.iter().filter(|(_, f)| *f == 0))
+        {
+            let accuracy = p[2] / max[2];
+            let color = if *front == 0 { GREEN } else { GRAY };
+            draw_circle(
+                left + w * p[0] / max[0],
+                top + h * (1. - p[1] / max[1]),
+                2. + 3. * accuracy,
+                Color::new(color.r, color.g, color.b, 0.3 + 0.7 * accuracy),
+            );
+        }
+
+        draw_text(
+            &format!("{:.0}", max[1]),
+            -width * 0.5 + 8.,
+            top + 4.,
+            16.,
+            WHITE,
+        );
+        draw_text("0", -width * 0.5 + 8., top + h + 4., 16., WHITE);
+        draw_text("0", left, top + h + 16., 16., WHITE);
+        let last = format!("{:.0}", max[0]);
+        let m = measure_text(&last, None, 16, 1.);
+        draw_text(&last, left + w - m.width, top + h + 16., 16., WHITE);
+        draw_text("Kills / Lifespan", left, top - 12., 16., WHITE);
+        let front = self.pareto.iter().filter(|(_, f)| *f == 0).count();
+        let str = format!("Front: {}", front);
+        let m = measure_text(&str, None, 16, 1.);
+        draw_rectangle(left + w - m.width - 15., top - 20., 10., 10., GREEN);
+        draw_text(&str, left + w - m.width, top - 11., 16., WHITE);
+    }
+}
+
+impl World {
+    fn color(&self) -> Color {
+        if self.track {
+            Color::new(0., 0.8, 0., 0.8)
+        } else if self.player.brain.is_none() {
+            Color::new(1., 1., 1., 0.8)
+        } else {
+            Color::new(1., 1., 1., 0.4)
+        }
+    }
+
+    pub fn draw(&self, debug: bool) {
+        let color = self.color();
+        self.player.draw(color, debug);
+        for asteroid in &self.asteroids {
+            asteroid.draw(color);
+        }
+        draw_text(
+            &format!("{:.2}", self.fitness),
+            self.player.pos.x - 22.,
+            self.player.pos.y - 20.,
+            12.,
+            WHITE,
+        );
+    }
+
+    pub fn draw_stats(&self, width: f32, height: f32, rank: usize) {
+        draw_rectangle_lines(-width * 0.5, -height * 0.5, width, height, 2., WHITE);
+
+        let scale = 2.5;
+        let offset = vec2(-width * 0.3, -height * 0.1);
+        let p1 = scale * vec2(0., -20.) + offset;
+        let p2: Vec2 = scale * vec2(-12.667, 18.) + offset;
+        let p3 = scale * vec2(12.667, 18.) + offset;
+        let p4 = scale * vec2(-10., 10.) + offset;
+        let p5 = scale * vec2(10., 10.) + offset;
+        let p6 = scale * vec2(0., 25.) + offset;
+        let p7 = scale * vec2(-6., 10.) + offset;
+        let p8 = scale * vec2(6., 10.) + offset;
+
+        draw_line(p1.x, p1.y, p2.x, p2.y, 2., WHITE);
+        draw_line(p1.x, p1.y, p3.x, p3.y, 2., WHITE);
+        draw_line(p4.x, p4.y, p5.x, p5.y, 2., WHITE);
+        if self.player.outputs[2] > 0. && (gen_range(0., 1.) < 0.4 || self.over) {
+            draw_triangle_lines(p6, p7, p8, 2., WHITE);
+        }
+        let l1 = scale * vec2(30., 0.) + offset;
+        let l2 = scale * vec2(25., -5.) + offset;
+        let l3 = scale * vec2(25., 5.) + offset;
+        if self.player.outputs[0] > 0. {
+            draw_line(l1.x, l1.y, l2.x, l2.y, 2., WHITE);
+            draw_line(l1.x, l1.y, l3.x, l3.y, 2., WHITE);
+        }
+        let l1 = -scale * vec2(30., 0.) + offset;
+        let l2 = -scale * vec2(25., -5.) + offset;
+        let l3 = -scale * vec2(25., 5.) + offset;
+        if self.player.outputs[1] > 0. {
+            draw_line(l1.x, l1.y, l2.x, l2.y, 2., WHITE);
+            draw_line(l1.x, l1.y, l3.x, l3.y, 2., WHITE);
+        }
+        let l1 = -scale * vec2(0., 35.) + offset;
+        if self.player.outputs[3] > 0. {
+            draw_circle(l1.x, l1.y, 5., WHITE);
+            draw_circle(l1.x, l1.y, 3.5, BLACK);
+        }
+        draw_text(
+            if self.over { "DEAD" } else { "ALIVE" },
+            -width * 0.5 + 20.,
+            55.,
+            24.,
+            if self.over { RED } else { GREEN },
+        );
+        draw_text(
+            &format!("Hits: {}", self.score),
+            -width * 0.5 + 20.,
+            75.,
+            24.,
+            WHITE,
+        );
+        draw_text(
+            &format!("Fired: {}", self.player.shots),
+            -width * 0.5 + 20.,
+            95.,
+            24.,
+            WHITE,
+        );
+        draw_text(
+            &format!("Fitness: {:.2}", self.fitness),
+            -width * 0.5 + 20.,
+            115.,
+            24.,
+            WHITE,
+        );
+        draw_text(
+            &format!("Lifetime: {:.2}", self.player.lifespan as f32 / 60.),
+            -width * 0.5 + 20.,
+            135.,
+            24.,
+            WHITE,
+        );
+        let str = &format!("RANK #{}", rank);
+        let w = measure_text(str, None, 64, 0.5);
+
+        draw_text(str, -w.width * 0.5, -height * 0.35, 32., WHITE);
+    }
+}
+
+impl Player {
+    pub fn draw(&self, color: Color, debug: bool) {
+        let p1 = self.pos + self.dir * 20.;
+        let p2 = self.pos + self.dir.rotate(vec2(-18., -12.667));
+        let p3 = self.pos + self.dir.rotate(vec2(-18., 12.667));
+        let p4 = self.pos + self.dir.rotate(vec2(-10., -10.));
+        let p5 = self.pos + self.dir.rotate(vec2(-10., 10.));
+        let p6 = self.pos + self.dir * -25.;
+        let p7 = self.pos + self.dir.rotate(vec2(-10., -6.));
+        let p8 = self.pos + self.dir.rotate(vec2(-10., 6.));
+        draw_line(p1.x, p1.y, p2.x, p2.y, 2., color);
+        draw_line(p1.x, p1.y, p3.x, p3.y, 2., color);
+        draw_line(p4.x, p4.y, p5.x, p5.y, 2., color);
+        if self.acc > 0. && gen_range(0., 1.) < 0.4 {
+            draw_triangle_lines(p6, p7, p8, 2., color);
+        }
+        if debug {
+            if let Some(ast) = self.asteroid.as_ref() {
+                draw_circle_lines(ast.pos.x, ast.pos.y, ast.radius, 1., RED);
+                // let p = self.pos
+                //     + self.dir.rotate(Vec2::from_angle(self.asteroid_data[0].1))
+                //         * self.asteroid_data[0].0
+                //         * WIDTH;
+                draw_line(self.pos.x, self.pos.y, ast.pos.x, ast.pos.y, 1., RED);
+            }
+
+            // Draw raycasts
+
+            // for (i, r) in self.raycasts.iter().enumerate() {
+            //     let dir = Vec2::from_angle(PI / 4. * i as f32).rotate(self.dir);
+            //     draw_line(
+            //         self.pos.x,
+            //         self.pos.y,
+            //         self.pos.x + dir.x * 100. / r,
+            //         self.pos.y + dir.y * 100. / r,
+            //         1.,
+            //         GRAY,
+            //     );
+            // }
+        }
+
+        for bullet in &self.bullets {
+            bullet.draw(color);
+        }
+    }
+
+    pub fn draw_brain(&self, width: f32, height: f32, bias: bool) {
+        if let Some(brain) = &self.brain {
+            brain.draw(width, height, &self.inputs, &self.outputs, bias);
+        }
+    }
+}
+
+impl Bullet {
+    fn draw(&self, c: Color) {
+        draw_circle(self.pos.x, self.pos.y, 2., Color::new(c.r, c.g, c.b, 0.9));
+    }
+}
+
+impl Asteroid {
+    pub fn draw(&self, color: Color) {
+        draw_poly_lines(
+            self.pos.x,
+            self.pos.y,
+            self.sides,
+            self.radius,
+            self.rot,
+            match self.size {
+                AsteroidSize::Large => 2.,
+                AsteroidSize::Medium => 1.2,
+                AsteroidSize::Small => 1.,
+            },
+            color,
+        );
+    }
+}
+
+impl Brain {
+    pub fn draw(&self, width: f32, height: f32, inputs: &[f32], outputs: &[f32], bias: bool) {
+        match self {
+            Brain::NN(nn) => nn.draw(width, height, inputs, outputs, bias),
+            Brain::Neat(genome) => genome.draw(width, height, inputs, outputs, bias),
+        }
+    }
+}
+
+impl Genome {
+    // Nodes are placed in columns by the longest path reaching them from the inputs
+    pub fn draw(&self, width: f32, height: f32, inputs: &[f32], outputs: &[f32], bias: bool) {
+        draw_rectangle_lines(-width * 0.5, -height * 0.5, width, height, 2., WHITE);
+
+        let width = width * 0.8;
+        let height = height * 0.8;
+        let mut depth = std::collections::HashMap::new();
+        for node in 0..=self.inputs {
+            depth.insert(node, 0);
+        }
+        for &node in &self.hidden {
+            let d = self
+                .conns
+                .iter()
+                .filter(|c| c.enabled && c.to == node)
+                .filter_map(|c| depth.get(&c.from))
+                .max()
+                .map_or(1, |d| d + 1);
+            depth.insert(node, d);
+        }
+        let last = depth.values().max().unwrap() + 1;
+        let mut columns = vec![vec![]; last + 1];
+        columns[0] = (0..self.inputs + bias as usize).collect();
+        for &node in &self.hidden {
+            columns[depth[&node]].push(node);
+        }
+        columns[last] = (self.inputs + 1..=self.inputs + self.outputs).collect();
+
+        let vspace = height / (columns.iter().map(|c| c.len()).max().unwrap() - 1).max(1) as f32;
+        let mut pos = std::collections::HashMap::new();
+        for (i, column) in columns.iter().enumerate() {
+            for (j, &node) in column.iter().enumerate() {
+                pos.insert(
+                    node,
+                    (
+                        i as f32 * width / last as f32 - width * 0.5,
+                        j as f32 * vspace - (vspace * (column.len() as f32 - 1.)) * 0.5,
+                    ),
+                );
+            }
+        }
+        for conn in self.conns.iter().filter(|c| c.enabled) {
+            if let (Some(p1), Some(p2)) = (pos.get(&conn.from), pos.get(&conn.to)) {
+                let c = if conn.weight < 0. { 0. } else { 1. };
+                draw_line(
+                    p1.0,
+                    p1.1,
+                    p2.0,
+                    p2.1,
+                    1.5,
+                    Color::new(1., c, c, conn.weight.abs()),
+                );
+            }
+        }
+
+        let mut inputs = inputs.to_vec();
+        inputs.push(1.);
+        for (i, column) in columns.iter().enumerate() {
+            for (j, node) in column.iter().enumerate() {
+                let p = pos[node];
+                draw_circle(p.0, p.1, 10., WHITE);
+                draw_circle(p.0, p.1, 8., BLACK);
+                if i == 0 && inputs.len() > 1 {
+                    let c = if inputs[j] < 0. { 0. } else { 1. };
+                    draw_circle(p.0, p.1, 8., Color::new(1., c, c, inputs[j].abs()));
+                    draw_text(
+                        &format!("{:.2}", inputs[j]),
+                        p.0 - if inputs[j] < 0. { 50. } else { 42. },
+                        p.1 + 4.,
+                        16.,
+                        WHITE,
+                    );
+                } else if i == last && !outputs.is_empty() {
+                    draw_circle(p.0, p.1, 8., Color::new(1., 1., 1., outputs[j]));
+                    draw_text(
+                        &format!("{:.2}", outputs[j]),
+                        p.0 + 14.,
+                        p.1 + 4.,
+                        16.,
+                        WHITE,
+                    );
+                }
+            }
+        }
+        draw_rectangle(width * 0.47, height * 0.47, 10., 10., RED);
+        draw_text("-ve", width * 0.47 + 20., height * 0.47 + 10., 20.0, WHITE);
+        draw_rectangle(width * 0.47, height * 0.47 + 20., 10., 10., WHITE);
+        draw_text("+ve", width * 0.47 + 20., height * 0.47 + 30., 20.0, WHITE);
+    }
+}
+
+impl NN {
+    pub fn draw(&self, width: f32, height: f32, inputs: &[f32], outputs: &[f32], bias: bool) {
+        draw_rectangle_lines(-width * 0.5, -height * 0.5, width, height, 2., WHITE);
+
+        let width = width * 0.8;
+        let height = height * 0.8;
+        let vspace = height / (self.config.iter().max().unwrap() - 1) as f32;
+        let mut p1s: Vec<(f32, f32)>;
+        let mut p2s: Vec<(f32, f32)> = Vec::new();
+        for (i, layer) in self
+            .config
+            .iter()
+            .take(self.config.len() - 1)
+            .map(|x| x - if bias { 0 } else { 1 })
+            .chain(self.config.last().copied())
+            .enumerate()
+        {
+            p1s = p2s;
+            p2s = Vec::new();
+            for neuron in 0..layer {
+                p2s.push((
+                    i as f32 * width / (self.config.len() - 1) as f32 - width * 0.5,
+                    neuron as f32 * vspace - (vspace * (layer - 1) as f32) * 0.5,
+                ));
+            }
+            // Loop above every recurrent neuron showing the weight of its own previous output
+            if let Some(u) = i.checked_sub(1).and_then(|l| self.recurrent.get(l)) {
+                for (j, p) in p2s.iter().take(u.nrows()).enumerate() {
+                    let weight = *u.index((j, j));
+                    let c = if weight < 0. { 0. } else { 1. };
+                    draw_circle_lines(p.0, p.1 - 14., 7., 1.5, Color::new(1., c, c, weight.abs()));
+                }
+            }
+            for (k, j, p1, p2) in p1s.iter().enumerate().flat_map(|(k, x)| {
+                p2s.iter()
+                    .take(
+                        p2s.len()
+                            - if i == self.config.len() - 1 || !bias {
+                                0
+                            } else {
+                                1
+                            },
+                    )
+                    .enumerate()
+                    .map(move |(j, y)| (k, j, *x, *y))
+            }) {
+                let weight = *self.weights[i - 1].index((j, k));
+                let c = if weight < 0. { 0. } else { 1. };
+                draw_line(
+                    p1.0,
+                    p1.1,
+                    p2.0,
+                    p2.1,
+                    1.5,
+                    Color::new(1., c, c, weight.abs()),
+                );
+            }
+
+            let mut inputs = inputs.to_vec();
+            inputs.push(1.);
+
+            for (j, p) in p1s.iter().enumerate() {
+                draw_circle(p.0, p.1, 10., WHITE);
+                draw_circle(p.0, p.1, 8., BLACK);
+                draw_circle(
+                    p.0,
+                    p.1,
+                    8.,
+                    if i == 1 && inputs.len() > 1 {
+                        let c = if inputs[j] < 0. { 0. } else { 1. };
+                        Color::new(1., c, c, inputs[j].abs())
+                    } else {
+                        BLACK
+                    },
+                );
+                if i == 1 && inputs.len() > 1 {
+                    draw_text(
+                        &format!("{:.2}", inputs[j]),
+                        p.0 - if inputs[j] < 0. { 50. } else { 42. },
+                        p.1 + 4.,
+                        16.,
+                        WHITE,
+                    );
+                }
+            }
+        }
+        for (j, p) in p2s.iter().enumerate() {
+            draw_circle(p.0, p.1, 10., WHITE);
+            draw_circle(p.0, p.1, 8., BLACK);
+            if !outputs.is_empty() {
+                draw_circle(p.0, p.1, 8., Color::new(1., 1., 1., outputs[j]));
+                draw_text(
+                    &format!("{:.2}", outputs[j]),
+                    p.0 + 14.,
+                    p.1 + 4.,
+                    16.,
+                    WHITE,
+                );
+            }
+        }
+        draw_rectangle(width * 0.47, height * 0.47, 10., 10., RED);
+        draw_text("-ve", width * 0.47 + 20., height * 0.47 + 10., 20.0, WHITE);
+        draw_rectangle(width * 0.47, height * 0.47 + 20., 10., 10., WHITE);
+        draw_text("+ve", width * 0.47 + 20., height * 0.47 + 30., 20.0, WHITE);
+    }
+}
@@ -0,0 +1,85 @@
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+const MULTIPLIER: u64 = 6364136223846793005;
+
+// PCG-XSH-RR generator. Unlike macroquad's global rand, every owner keeps its own state,
+// so a run can be reproduced exactly from its seed and stream.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rng {
+    state: u64,
+    inc: u64,
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::new(0, 0)
+    }
+}
+
+impl Rng {
+    // Generators with the same seed but different streams produce unrelated sequences
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            inc: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    // New independent generator seeded from this one
+    pub fn fork(&mut self) -> Self {
+        Self::new(self.next_u64(), self.next_u64())
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(self.inc);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        ((self.next_u32() as u64) << 32) | self.next_u32() as u64
+    }
+
+    // Uniform in [low, high)
+    pub fn gen_range<T: RandomRange>(&mut self, low: T, high: T) -> T {
+        T::gen_range(self, low, high)
+    }
+
+    // Standard normal sample (Box-Muller transform)
+    pub fn gen_normal(&mut self) -> f32 {
+        let u = 1. - self.gen_range(0., 1.);
+        (-2. * u.ln()).sqrt() * (PI * self.gen_range(0., 2.)).cos()
+    }
+}
+
+pub trait RandomRange {
+    fn gen_range(rng: &mut Rng, low: Self, high: Self) -> Self;
+}
+
+impl RandomRange for f32 {
+    fn gen_range(rng: &mut Rng, low: Self, high: Self) -> Self {
+        // 24 random bits fill the mantissa exactly
+        let r = (rng.next_u32() >> 8) as f32 / (1 << 24) as f32;
+        low + (high - low) * r
+    }
+}
+
+macro_rules! impl_int_range {
+    ($($t:ty),*) => {$(
+        impl RandomRange for $t {
+            fn gen_range(rng: &mut Rng, low: Self, high: Self) -> Self {
+                let span = (high - low) as u64;
+                low + ((rng.next_u32() as u64 * span) >> 32) as $t
+            }
+        }
+    )*};
+}
+
+impl_int_range!(u8, u32, usize);
@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+
+use crate::rng::Rng;
+
+// How parents are picked for the next generation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Selection {
+    // Fitness proportional
+    #[default]
+    Roulette,
+    // Best of k ships picked at random
+    Tournament {
+        k: usize,
+    },
+    // Proportional to position when sorted by fitness, so one ship cannot dominate
+    Rank,
+    // Fitness proportional with evenly spaced pointers, less noisy than roulette
+    StochasticUniversal,
+    // Uniformly from the best fraction
+    Truncation {
+        frac: f32,
+    },
+}
+
+impl Selection {
+    // Indices of `n` parents chosen from candidates with the given fitness
+    pub fn select(&self, fitness: &[f32], n: usize, rng: &mut Rng) -> Vec<usize> {
+        let mut order = (0..fitness.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| fitness[b].total_cmp(&fitness[a]));
+        match *self {
+            Selection::Roulette => match shifted(fitness) {
+                Some(weights) => (0..n).map(|_| roulette(&weights, rng)).collect(),
+                None => uniform(fitness.len(), n, rng),
+            },
+            Selection::Tournament { k } => (0..n)
+                .map(|_| {
+                    (0..k.max(1))
+                        .map(|_| rng.gen_range(0, fitness.len()))
+                        .max_by(|&a, &b| fitness[a].total_cmp(&fitness[b]))
+                        .unwrap()
+                })
+                .collect(),
+            Selection::Rank => {
+                // Best gets weight len, worst gets 1
+                let mut weights = vec![0.; fitness.len()];
+                for (rank, &i) in order.iter().enumerate() {
+                    weights[i] = (fitness.len() - rank) as f32;
+                }
+                (0..n).map(|_| roulette(&weights, rng)).collect()
+            }
+            Selection::StochasticUniversal => {
+                let Some(fitness) = shifted(fitness) else {
+                    return uniform(fitness.len(), n, rng);
+                };
+                let total = fitness.iter().sum::<f32>();
+                let step = total / n as f32;
+                let start = rng.gen_range(0., step);
+                let mut picks = Vec::with_capacity(n);
+                let (mut i, mut sum) = (0, fitness[0]);
+                for p in 0..n {
+                    let pointer = start + p as f32 * step;
+                    while sum < pointer && i < fitness.len() - 1 {
+                        i += 1;
+                        sum += fitness[i];
+                    }
+                    picks.push(i);
+                }
+                // Pointers come out in order, shuffle so that neighbours do not always mate
+                for j in (1..picks.len()).rev() {
+                    picks.swap(j, rng.gen_range(0, j + 1));
+                }
+                picks
+            }
+            Selection::Truncation { frac } => {
+                let count = ((fitness.len() as f32 * frac).ceil() as usize).clamp(1, fitness.len());
+                (0..n).map(|_| order[rng.gen_range(0, count)]).collect()
+            }
+        }
+    }
+}
+
+// Fitness shifted up so none is negative, None when there is nothing to be proportional to
+fn shifted(fitness: &[f32]) -> Option<Vec<f32>> {
+    let low = fitness.iter().fold(0f32, |low, &f| low.min(f));
+    let weights = fitness.iter().map(|f| f - low).collect::<Vec<_>>();
+    (weights.iter().sum::<f32>() > 0.).then_some(weights)
+}
+
+fn uniform(len: usize, n: usize, rng: &mut Rng) -> Vec<usize> {
+    (0..n).map(|_| rng.gen_range(0, len)).collect()
+}
+
+fn roulette(weights: &[f32], rng: &mut Rng) -> usize {
+    let r = rng.gen_range(0., weights.iter().sum());
+    let mut sum = 0.;
+    for (i, w) in weights.iter().enumerate() {
+        sum += w;
+        if sum >= r {
+            return i;
+        }
+    }
+    weights.len() - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Selection; 5] = [
+        Selection::Roulette,
+        Selection::Tournament { k: 3 },
+        Selection::Rank,
+        Selection::StochasticUniversal,
+        Selection::Truncation { frac: 0.5 },
+    ];
+
+    fn counts(selection: Selection, fitness: &[f32], n: usize) -> Vec<usize> {
+        let mut rng = Rng::new(7, 0);
+        let mut counts = vec![0; fitness.len()];
+        for i in selection.select(fitness, n, &mut rng) {
+            counts[i] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn picks_are_in_range() {
+        for selection in ALL {
+            for fitness in [vec![1.], vec![5., 0., 2.5, 9.], vec![-3., -1., -2.]] {
+                let picks = selection.select(&fitness, 50, &mut Rng::new(1, 0));
+                assert_eq!(picks.len(), 50);
+                assert!(picks.iter().all(|&i| i < fitness.len()), "{:?}", selection);
+            }
+        }
+    }
+
+    #[test]
+    fn zero_fitness_picks_everyone() {
+        for selection in [Selection::Roulette, Selection::StochasticUniversal] {
+            let counts = counts(selection, &[0.; 8], 800);
+            assert!(
+                counts.iter().all(|&c| c > 50),
+                "{:?}: {:?}",
+                selection,
+                counts
+            );
+        }
+    }
+
+    #[test]
+    fn negative_fitness_favours_the_fittest() {
+        for selection in [Selection::Roulette, Selection::StochasticUniversal] {
+            let counts = counts(selection, &[-10., -1., -6.], 900);
+            assert_eq!(counts[0], 0, "{:?}", selection);
+            assert!(counts[1] > counts[2], "{:?}: {:?}", selection, counts);
+        }
+    }
+
+    #[test]
+    fn proportional_to_fitness() {
+        let counts = counts(Selection::StochasticUniversal, &[1., 3.], 400);
+        assert_eq!(counts, vec![100, 300]);
+    }
+}
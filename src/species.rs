@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{brain::Brain, world::World};
+
+// Ships with similar brains, which compete for children mostly among themselves so new
+// structure has time to tune its weights before it has to beat the rest of the population
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Species {
+    pub id: usize,
+    // Fittest member of the last generation, new ships are compared to it
+    representative: Brain,
+    // Indices of the members in the sorted worlds of the generation just finished
+    #[serde(skip)]
+    pub members: Vec<usize>,
+    // Best fitness the species ever reached and generations since it last improved
+    pub best: f32,
+    pub stale: u32,
+}
+
+// Puts every ship in the first species whose representative is closer than `threshold`, or
+// in a new one. `worlds` must be sorted by fitness.
+pub fn speciate(species: &mut Vec<Species>, worlds: &[World], threshold: f32, next_id: &mut usize) {
+    for s in species.iter_mut() {
+        s.members.clear();
+    }
+    for (i, world) in worlds.iter().enumerate() {
+        let brain = world.see_brain();
+        match species
+            .iter_mut()
+            .find(|s| Brain::distance(&s.representative, brain) < threshold)
+        {
+            Some(s) => s.members.push(i),
+            None => {
+                species.push(Species {
+                    id: *next_id,
+                    representative: brain.clone(),
+                    members: vec![i],
+                    best: f32::MIN,
+                    stale: 0,
+                });
+                *next_id += 1;
+            }
+        }
+    }
+    species.retain(|s| !s.members.is_empty());
+    for s in species.iter_mut() {
+        s.representative = worlds[s.members[0]].see_brain().clone();
+        let top = worlds[s.members[0]].fitness;
+        if top > s.best {
+            s.best = top;
+            s.stale = 0;
+        } else {
+            s.stale += 1;
+        }
+    }
+}
+
+// Splits `total` children between groups in proportion to their mean fitness, which is the
+// sum of their fitness shared among members (fitness sharing), so a big species cannot take
+// over just by its size. Means are shifted up so the lowest is never below zero. Rounding
+// leftovers go to the largest remainders.
+pub fn offspring(groups: &[Vec<usize>], fitness: &[f32], total: usize) -> Vec<usize> {
+    let means = groups
+        .iter()
+        .map(|g| g.iter().map(|&i| fitness[i]).sum::<f32>() / g.len() as f32)
+        .collect::<Vec<_>>();
+    let low = means.iter().fold(0f32, |low, &m| low.min(m));
+    let means = means.iter().map(|m| m - low).collect::<Vec<_>>();
+    let sum = means.iter().sum::<f32>();
+    let exact = means
+        .iter()
+        .map(|m| match sum > 0. {
+            true => m / sum * total as f32,
+            false => total as f32 / groups.len() as f32,
+        })
+        .collect::<Vec<_>>();
+    let mut counts = exact
+        .iter()
+        .map(|&e| (e.floor() as usize).min(total))
+        .collect::<Vec<_>>();
+    let mut order = (0..groups.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| (exact[b] - exact[b].floor()).total_cmp(&(exact[a] - exact[a].floor())));
+    let left = total.saturating_sub(counts.iter().sum());
+    for &i in order.iter().cycle().take(left) {
+        counts[i] += 1;
+    }
+    // Float error can round the shares past the total
+    while counts.iter().sum::<usize>() > total {
+        let largest = (0..counts.len()).max_by_key(|&i| counts[i]).unwrap();
+        counts[largest] -= 1;
+    }
+    debug_assert!(groups.is_empty() || counts.iter().sum::<usize>() == total);
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offspring_add_up_to_total() {
+        let groups = vec![vec![0, 1], vec![2], vec![3, 4, 5]];
+        let cases = [
+            vec![10., 20., 5., 1., 2., 3.],
+            vec![0.; 6],
+            vec![-50., -10., 30., 1., 2., 3.],
+            vec![-5., -6., -7., -8., -9., -1.],
+        ];
+        for fitness in &cases {
+            for total in [0, 1, 7, 40, 333] {
+                let counts = offspring(&groups, fitness, total);
+                assert_eq!(counts.iter().sum::<usize>(), total, "{:?}", fitness);
+            }
+        }
+    }
+
+    #[test]
+    fn offspring_follow_mean_fitness() {
+        let groups = vec![vec![0], vec![1]];
+        assert_eq!(offspring(&groups, &[3., 1.], 8), vec![6, 2]);
+        assert_eq!(offspring(&groups, &[0., 0.], 8), vec![4, 4]);
+        // The worst group is shifted to zero
+        assert_eq!(offspring(&groups, &[-4., 4.], 8), vec![0, 8]);
+    }
+}
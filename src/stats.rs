@@ -0,0 +1,132 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::world::World;
+
+// Summary of one finished generation
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct GenStats {
+    // Generations completed, counting this one
+    pub gen: i32,
+    pub best: f32,
+    pub mean: f32,
+    pub median: f32,
+    pub min: f32,
+    pub std_dev: f32,
+    // Averages over all ships, lifespan is in frames
+    pub lifespan: f32,
+    pub accuracy: f32,
+    pub destroyed: f32,
+    // Seconds taken to simulate the generation
+    pub time: f32,
+}
+
+impl GenStats {
+    pub fn new(gen: i32, worlds: &[World], time: f32) -> Self {
+        let n = worlds.len() as f32;
+        let mut fitness = worlds.iter().map(|w| w.fitness).collect::<Vec<_>>();
+        fitness.sort_by(|a, b| a.total_cmp(b));
+        let mean = fitness.iter().sum::<f32>() / n;
+        let mid = fitness.len() / 2;
+        Self {
+            gen,
+            best: *fitness.last().unwrap(),
+            mean,
+            median: if fitness.len() % 2 == 0 {
+                (fitness[mid - 1] + fitness[mid]) * 0.5
+            } else {
+                fitness[mid]
+            },
+            min: fitness[0],
+            std_dev: (fitness.iter().map(|f| (f - mean).powi(2)).sum::<f32>() / n).sqrt(),
+            lifespan: worlds.iter().map(|w| w.player.lifespan as f32).sum::<f32>() / n,
+            accuracy: worlds
+                .iter()
+                .map(|w| w.score / w.player.shots as f32)
+                .sum::<f32>()
+                / n,
+            // Score starts at 1
+            destroyed: worlds.iter().map(|w| w.score - 1.).sum::<f32>() / n,
+            time,
+        }
+    }
+}
+
+// Seconds since the epoch, wall time is not measured on the web
+pub(crate) fn now() -> f64 {
+    #[cfg(not(target_arch = "wasm32"))]
+    return std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0., |d| d.as_secs_f64());
+    #[cfg(target_arch = "wasm32")]
+    0.
+}
+
+// Writes records as CSV if the path ends in .csv and as JSON Lines otherwise
+pub struct Log {
+    file: BufWriter<File>,
+    csv: bool,
+}
+
+impl Log {
+    // `header` names the CSV columns
+    pub fn create(path: &str, header: &str) -> Self {
+        let mut log = Self {
+            file: BufWriter::new(File::create(path).expect("Unable to create file")),
+            csv: path.to_lowercase().ends_with(".csv"),
+        };
+        if log.csv {
+            writeln!(log.file, "{}", header).expect("Unable to write file");
+        }
+        log
+    }
+
+    // `row` gives the CSV line of the record
+    pub fn write<T: Serialize>(&mut self, record: &T, row: impl FnOnce(&T) -> String) {
+        let line = match self.csv {
+            true => row(record),
+            false => serde_json::to_string(record).unwrap(),
+        };
+        writeln!(self.file, "{}", line).expect("Unable to write file");
+    }
+
+    // Keeps the file readable while training is still running
+    pub fn flush(&mut self) {
+        self.file.flush().expect("Unable to write file");
+    }
+}
+
+// Writes one record per generation
+pub struct StatsLog(Log);
+
+impl StatsLog {
+    pub fn create(path: &str) -> Self {
+        Self(Log::create(
+            path,
+            "gen,best,mean,median,min,std_dev,lifespan,accuracy,destroyed,time",
+        ))
+    }
+
+    pub fn write(&mut self, s: &GenStats) {
+        self.0.write(s, |s| {
+            format!(
+                "{},{},{},{},{},{},{},{},{},{}",
+                s.gen,
+                s.best,
+                s.mean,
+                s.median,
+                s.min,
+                s.std_dev,
+                s.lifespan,
+                s.accuracy,
+                s.destroyed,
+                s.time
+            )
+        });
+        self.0.flush();
+    }
+}
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{config::Config, stats::GenStats};
+
+// When training ends, checked after every generation. Any condition that is set can end it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StopConditions {
+    // Best fitness of a generation reaching this
+    pub target: Option<f32>,
+    // Generations completed
+    pub generations: Option<i32>,
+    // Generations in a row without a new best fitness
+    pub patience: Option<i32>,
+    // Seconds since the run started or was resumed, never reached on the web
+    pub seconds: Option<f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StopReason {
+    Target,
+    Generations,
+    Patience,
+    Time,
+}
+
+impl StopReason {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            StopReason::Target => "target fitness reached",
+            StopReason::Generations => "generation limit reached",
+            StopReason::Patience => "no improvement for too long",
+            StopReason::Time => "time limit reached",
+        }
+    }
+}
+
+// What a finished run achieved, saved next to its best brain
+#[derive(Serialize)]
+pub struct Summary<'a> {
+    pub reason: Option<StopReason>,
+    pub gen: i32,
+    pub best: f32,
+    // Best fitness of any generation and the generation it was scored in
+    pub best_ever: f32,
+    pub best_gen: i32,
+    pub seconds: f32,
+    pub last: Option<&'a GenStats>,
+    pub config: &'a Config,
+}
+
+impl Summary<'_> {
+    pub fn save(&self, path: &str) {
+        let json = serde_json::to_string_pretty(self).unwrap();
+        std::fs::write(path, json).expect("Unable to write file");
+    }
+}
@@ -1,62 +1,123 @@
 use crate::{
     asteroids::{Asteroid, AsteroidSize},
-    nn::{ActivationFunc, NN},
+    brain::Brain,
+    fitness::FitnessFn,
+    nn::ActivationFunc,
+    pareto::Objectives,
     player::Player,
+    rng::Rng,
 };
-use macroquad::{prelude::*, rand::gen_range};
+use glam::{vec2, Vec2};
 
 #[derive(Default)]
 pub struct World {
     pub player: Player,
-    asteroids: Vec<Asteroid>,
+    pub(crate) asteroids: Vec<Asteroid>,
     pub score: f32,
     pub over: bool,
     pub fitness: f32,
+    pub fitness_fn: FitnessFn,
     pub track: bool,
-    color: Color,
+    // Seed of the asteroid field, the same seed replays the same field
+    pub seed: u64,
+    rng: Rng,
+    // Generator of the periodic spawns when it is kept apart from the one splitting
+    // asteroids
+    spawns: Option<Rng>,
+    // Objectives combined over all episodes the brain played, once they are scored
+    pub(crate) scored: Option<Objectives>,
+    // Id of the brain in the genealogy of the population
+    pub id: u64,
+    // Island of the population the ship lives on
+    pub island: usize,
+    // Position every second, as fractions of the arena
+    pub(crate) trajectory: Vec<Vec2>,
 }
 
+// Samples of the trajectory kept for novelty search
+const TRAJECTORY: usize = 16;
+
 impl World {
     pub fn new(
         hlayers: Option<Vec<usize>>,
         mut_rate: Option<f32>,
         activ: Option<ActivationFunc>,
         (WIDTH, HEIGHT): (f32, f32),
+        rng: &mut Rng,
     ) -> Self {
+        let brain = hlayers
+            .map(|h| Brain::NN(Player::new_brain(h, mut_rate.unwrap(), activ.unwrap(), rng)));
+        World::spawn(brain, rng.next_u64(), (WIDTH, HEIGHT))
+    }
+    pub fn simulate(brain: Brain, (WIDTH, HEIGHT): (f32, f32), seed: u64) -> Self {
+        World::spawn(Some(brain), seed, (WIDTH, HEIGHT))
+    }
+
+    // The asteroid field only depends on the seed, not on how the brain was made
+    fn spawn(brain: Option<Brain>, seed: u64, (WIDTH, HEIGHT): (f32, f32)) -> Self {
+        let mut rng = Rng::new(seed, 0);
         Self {
-            color: Color::new(1., 1., 1., if hlayers.is_none() { 0.8 } else { 0.4 }),
-            player: Player::new(hlayers, mut_rate, activ),
+            player: Player::new(brain),
             score: 1.,
             asteroids: vec![
-                Asteroid::new_to(vec2(0., 0.), 1.5, AsteroidSize::Large, (WIDTH, HEIGHT)),
-                Asteroid::new(AsteroidSize::Large, (WIDTH, HEIGHT)),
-                Asteroid::new(AsteroidSize::Large, (WIDTH, HEIGHT)),
-                Asteroid::new(AsteroidSize::Large, (WIDTH, HEIGHT)),
-                Asteroid::new(AsteroidSize::Large, (WIDTH, HEIGHT)),
+                Asteroid::new_to(
+                    vec2(0., 0.),
+                    1.5,
+                    AsteroidSize::Large,
+                    (WIDTH, HEIGHT),
+                    &mut rng,
+                ),
+                Asteroid::new(AsteroidSize::Large, (WIDTH, HEIGHT), &mut rng),
+                Asteroid::new(AsteroidSize::Large, (WIDTH, HEIGHT), &mut rng),
+                Asteroid::new(AsteroidSize::Large, (WIDTH, HEIGHT), &mut rng),
+                Asteroid::new(AsteroidSize::Large, (WIDTH, HEIGHT), &mut rng),
             ],
+            seed,
+            rng,
             ..Default::default()
         }
     }
-    pub fn simulate(brain: NN, (WIDTH, HEIGHT): (f32, f32)) -> Self {
-        let mut w = World::new(None, None, None, (WIDTH, HEIGHT));
-        w.player.brain = Some(brain);
-        w.color = Color::new(1., 1., 1., 0.4);
-        w
-    }
 
     pub fn track(&mut self, track: bool) {
         self.track = track;
-        self.color = if track {
-            Color::new(0., 0.8, 0., 0.8)
-        } else {
-            Color::new(1., 1., 1., 0.4)
-        };
     }
 
-    pub fn see_brain(&self) -> &NN {
+    pub fn see_brain(&self) -> &Brain {
         self.player.brain.as_ref().unwrap()
     }
 
+    // What the ship did, for novelty search: where it went, with the last position repeated
+    // after death, and how often it pressed each key
+    pub fn behavior(&self) -> Vec<f32> {
+        let last = self.trajectory.last().copied().unwrap_or_default();
+        let lifespan = self.player.lifespan.max(1) as f32;
+        (0..TRAJECTORY)
+            .flat_map(|i| {
+                let p = self.trajectory.get(i).copied().unwrap_or(last);
+                [p.x, p.y]
+            })
+            .chain(self.player.presses.iter().map(|&p| p as f32 / lifespan))
+            .collect()
+    }
+
+    pub fn objectives(&self) -> Objectives {
+        if let Some(scored) = self.scored {
+            return scored;
+        }
+        // Score starts at 1
+        [
+            self.player.lifespan as f32,
+            self.score - 1.,
+            self.score / self.player.shots as f32,
+        ]
+    }
+
+    // Periodic spawns get their own generator, so every brain playing this seed meets the
+    // same ones however many asteroids it splits
+    pub fn share_spawns(&mut self) {
+        self.spawns = Some(Rng::new(self.seed, 1));
+    }
+
     pub fn export_brain(&self, path: &str) {
         let json = self.player.brain.as_ref().unwrap().export();
         std::fs::write(path, json).expect("Unable to write file");
@@ -64,6 +125,9 @@ impl World {
 
     pub fn update(&mut self, (WIDTH, HEIGHT): (f32, f32)) {
         self.player.update((WIDTH, HEIGHT));
+        if self.player.lifespan.is_multiple_of(60) && self.trajectory.len() < TRAJECTORY {
+            self.trajectory.push(self.player.pos / vec2(WIDTH, HEIGHT));
+        }
         let mut to_add: Vec<Asteroid> = Vec::new();
         for asteroid in &mut self.asteroids {
             asteroid.update((WIDTH, HEIGHT));
@@ -71,33 +135,39 @@ impl World {
                 self.score += 1.;
                 match asteroid.size {
                     AsteroidSize::Large => {
-                        let rand = vec2(gen_range(-0.8, 0.8), gen_range(-0.8, 0.8));
+                        let rand =
+                            vec2(self.rng.gen_range(-0.8, 0.8), self.rng.gen_range(-0.8, 0.8));
                         to_add.push(Asteroid::new_from(
                             asteroid.pos,
                             asteroid.vel + rand,
                             AsteroidSize::Medium,
                             (WIDTH, HEIGHT),
+                            &mut self.rng,
                         ));
                         to_add.push(Asteroid::new_from(
                             asteroid.pos,
                             asteroid.vel - rand,
                             AsteroidSize::Medium,
                             (WIDTH, HEIGHT),
+                            &mut self.rng,
                         ));
                     }
                     AsteroidSize::Medium => {
-                        let rand = vec2(gen_range(-0.6, 0.6), gen_range(-0.6, 0.6));
+                        let rand =
+                            vec2(self.rng.gen_range(-0.6, 0.6), self.rng.gen_range(-0.6, 0.6));
                         to_add.push(Asteroid::new_from(
                             asteroid.pos,
                             asteroid.vel + rand,
                             AsteroidSize::Small,
                             (WIDTH, HEIGHT),
+                            &mut self.rng,
                         ));
                         to_add.push(Asteroid::new_from(
                             asteroid.pos,
                             asteroid.vel - rand,
                             AsteroidSize::Small,
                             (WIDTH, HEIGHT),
+                            &mut self.rng,
                         ));
                     }
                     _ => {}
@@ -107,8 +177,9 @@ impl World {
                 self.over = true;
             }
         }
-        self.fitness =
-            (self.score / self.player.shots as f32).powi(2) * self.player.lifespan as f32;
+        self.fitness = self
+            .fitness_fn
+            .eval(self.player.lifespan, self.score, self.player.shots);
         self.asteroids.append(&mut to_add);
         self.asteroids.retain(|asteroid| asteroid.alive);
         // if self.asteroids.iter().fold(0, |acc, x| {
@@ -120,107 +191,14 @@ impl World {
         // }) < self.max_asteroids
         //     || self.player.lifespan % 200 == 0
         // {
-        if self.player.lifespan % 200 == 0 {
+        if self.player.lifespan.is_multiple_of(200) {
             self.asteroids.push(Asteroid::new_to(
                 self.player.pos,
                 1.5,
                 AsteroidSize::Large,
                 (WIDTH, HEIGHT),
+                self.spawns.as_mut().unwrap_or(&mut self.rng),
             ));
         }
     }
-
-    pub fn draw(&self, debug: bool) {
-        self.player.draw(self.color, debug);
-        for asteroid in &self.asteroids {
-            asteroid.draw(self.color);
-        }
-        draw_text(
-            &format!("{:.2}", self.fitness),
-            self.player.pos.x - 22.,
-            self.player.pos.y - 20.,
-            12.,
-            WHITE,
-        );
-    }
-
-    pub fn draw_stats(&self, width: f32, height: f32, rank: usize) {
-        draw_rectangle_lines(-width * 0.5, -height * 0.5, width, height, 2., WHITE);
-
-        let scale = 2.5;
-        let offset = vec2(-width * 0.3, -height * 0.1);
-        let p1 = scale * vec2(0., -20.) + offset;
-        let p2: Vec2 = scale * vec2(-12.667, 18.) + offset;
-        let p3 = scale * vec2(12.667, 18.) + offset;
-        let p4 = scale * vec2(-10., 10.) + offset;
-        let p5 = scale * vec2(10., 10.) + offset;
-        let p6 = scale * vec2(0., 25.) + offset;
-        let p7 = scale * vec2(-6., 10.) + offset;
-        let p8 = scale * vec2(6., 10.) + offset;
-
-        draw_line(p1.x, p1.y, p2.x, p2.y, 2., WHITE);
-        draw_line(p1.x, p1.y, p3.x, p3.y, 2., WHITE);
-        draw_line(p4.x, p4.y, p5.x, p5.y, 2., WHITE);
-        if self.player.outputs[2] > 0. && (gen_range(0., 1.) < 0.4 || self.over) {
-            draw_triangle_lines(p6, p7, p8, 2., WHITE);
-        }
-        let l1 = scale * vec2(30., 0.) + offset;
-        let l2 = scale * vec2(25., -5.) + offset;
-        let l3 = scale * vec2(25., 5.) + offset;
-        if self.player.outputs[0] > 0. {
-            draw_line(l1.x, l1.y, l2.x, l2.y, 2., WHITE);
-            draw_line(l1.x, l1.y, l3.x, l3.y, 2., WHITE);
-        }
-        let l1 = -scale * vec2(30., 0.) + offset;
-        let l2 = -scale * vec2(25., -5.) + offset;
-        let l3 = -scale * vec2(25., 5.) + offset;
-        if self.player.outputs[1] > 0. {
-            draw_line(l1.x, l1.y, l2.x, l2.y, 2., WHITE);
-            draw_line(l1.x, l1.y, l3.x, l3.y, 2., WHITE);
-        }
-        let l1 = -scale * vec2(0., 35.) + offset;
-        if self.player.outputs[3] > 0. {
-            draw_circle(l1.x, l1.y, 5., WHITE);
-            draw_circle(l1.x, l1.y, 3.5, BLACK);
-        }
-        draw_text(
-            if self.over { "DEAD" } else { "ALIVE" },
-            -width * 0.5 + 20.,
-            55.,
-            24.,
-            if self.over { RED } else { GREEN },
-        );
-        draw_text(
-            &format!("Hits: {}", self.score),
-            -width * 0.5 + 20.,
-            75.,
-            24.,
-            WHITE,
-        );
-        draw_text(
-            &format!("Fired: {}", self.player.shots),
-            -width * 0.5 + 20.,
-            95.,
-            24.,
-            WHITE,
-        );
-        draw_text(
-            &format!("Fitness: {:.2}", self.fitness),
-            -width * 0.5 + 20.,
-            115.,
-            24.,
-            WHITE,
-        );
-        draw_text(
-            &format!("Lifetime: {:.2}", self.player.lifespan as f32 / 60.),
-            -width * 0.5 + 20.,
-            135.,
-            24.,
-            WHITE,
-        );
-        let str = &format!("RANK #{}", rank);
-        let w = measure_text(str, None, 64, 0.5);
-
-        draw_text(str, -w.width * 0.5, -height * 0.35, 32., WHITE);
-    }
 }
@@ -0,0 +1,51 @@
+use genetic::{config::Config, population::Population, rng::Rng};
+
+const DIMS: (f32, f32) = (800., 600.);
+
+fn config() -> Config {
+    Config {
+        episodes: 2,
+        elite_evals: 2,
+        ..Default::default()
+    }
+}
+
+fn run(pop: &mut Population, gens: i32) {
+    let end = pop.gen + gens;
+    while pop.gen < end {
+        pop.update(DIMS);
+    }
+}
+
+// Fitness of every generation and every brain of the last one
+fn outcome(pop: &Population) -> (Vec<f32>, Vec<String>) {
+    (
+        pop.history.iter().map(|s| s.best).collect(),
+        pop.worlds.iter().map(|w| w.see_brain().export()).collect(),
+    )
+}
+
+#[test]
+fn same_seed_same_run_on_any_number_of_threads() {
+    let outcomes = [1, 4].map(|threads| {
+        let mut pop = Population::new(16, None, config(), DIMS, Rng::new(11, 0));
+        pop.set_threads(threads);
+        run(&mut pop, 2);
+        outcome(&pop)
+    });
+    assert_eq!(outcomes[0], outcomes[1]);
+}
+
+#[test]
+fn resumed_run_matches_uninterrupted_one() {
+    let mut pop = Population::new(16, None, config(), DIMS, Rng::new(12, 0));
+    run(&mut pop, 1);
+    let path = std::env::temp_dir().join(format!("genetic-resume-{}.json", std::process::id()));
+    let path = path.to_str().unwrap();
+    pop.save(path);
+    run(&mut pop, 1);
+    let mut resumed = Population::load(path, None, DIMS);
+    std::fs::remove_file(path).unwrap();
+    run(&mut resumed, 1);
+    assert_eq!(outcome(&pop), outcome(&resumed));
+}